@@ -0,0 +1,391 @@
+//! Command-line interface of the binary.
+
+use std::path::PathBuf;
+
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+
+use remove_old_arch_pkgs::{
+    AutoConfirmLevel, ComparatorKind, DisplayedTime, FollowSymlinks, Grouping, Options,
+    OutputFormat, DEFAULT_DBPATH, DEFAULT_PACMAN_CONF,
+};
+
+/// Removes the old versions of the packages of a pacman cache directory, keeping the newest
+/// ones and the installed ones.
+///
+/// Without a command, the cache is cleaned as with `clean`.
+#[derive(Debug, Parser)]
+#[command(version, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub clean: CleanArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Remove the old packages, asking about the ambiguous versions first (the default)
+    Clean(CleanArgs),
+    /// List what a cleaning would keep, remove and ignore, without asking nor removing anything
+    List(ListArgs),
+    /// Save what a cleaning would remove to a plan file, without asking nor removing anything,
+    /// to apply it later with `apply`
+    Plan(PlanArgs),
+    /// Count the packages and versions of the cache, and how much space a cleaning would free
+    Stats(ScanArgs),
+    /// Report the files that aren't packages, the signatures without package and the packages
+    /// without signature
+    Verify(ScanArgs),
+    /// List the versions the `alpm` and `version-compare` backends order differently
+    CompareBackends(ScanArgs),
+    /// Remove the files of a plan saved with `plan --output` or `clean --output`
+    Apply(ApplyArgs),
+    /// Put back the files moved away by the run recorded in a journal
+    Restore(RestoreArgs),
+    /// Remove the files that have been in a quarantine directory for too long
+    Purge(PurgeArgs),
+    /// Print the completion script of a shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// Which cache is scanned and how its versions are compared.
+#[derive(Debug, Args)]
+pub struct ScanArgs {
+    /// Cache directories, seen as one cache : only the newest versions among all of them are
+    /// kept. The `CacheDir` entries with `--config`, the current directory otherwise
+    pub dirs: Vec<PathBuf>,
+    /// Take the cache directories, database directory and ignored packages from pacman's
+    /// configuration FILE, `/etc/pacman.conf` if not given (the directories then go before
+    /// `-c`, or after `--`)
+    #[arg(
+        short = 'c',
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_PACMAN_CONF
+    )]
+    pub config: Option<PathBuf>,
+    /// Also scan the subdirectories for packages, as the clone trees of AUR helpers
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Scan at most N levels of subdirectories (implies `--recursive`)
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// Whether symbolic links to directories are scanned when recursing
+    #[arg(
+        long,
+        value_name = "POLICY",
+        default_value = "never",
+        value_parser = parser::<FollowSymlinks>(&["never", "always"])
+    )]
+    pub follow_symlinks: FollowSymlinks,
+    /// Skip the files and subdirectories whose name or relative path matches PATTERN
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+    /// Compare the versions of a package across all the directories, or within each directory
+    /// on its own
+    #[arg(
+        long,
+        value_name = "GROUPING",
+        default_value = "global",
+        value_parser = parser::<Grouping>(&["global", "per-directory"])
+    )]
+    pub group_by: Grouping,
+    /// Keep the N most recent versions of each package (0 removes every cached version)
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    pub keep: usize,
+    /// Keep only the N most recent versions of the packages that aren't installed anymore
+    #[arg(short = 'u', long, value_name = "N")]
+    pub keep_uninstalled: Option<usize>,
+    /// Remove the identical copies of the kept packages found in several directories, keeping
+    /// the one in the first directory
+    #[arg(long)]
+    pub remove_duplicates: bool,
+    /// Backend used to compare versions
+    #[arg(
+        long,
+        value_name = "BACKEND",
+        default_value = "alpm",
+        value_parser = parser::<ComparatorKind>(&["alpm", "version-compare"])
+    )]
+    pub comparator: ComparatorKind,
+    /// Pacman database directory, the installed versions are always kept. `DBPath` with
+    /// `--config`, `/var/lib/pacman/` otherwise
+    #[arg(long, value_name = "DIR")]
+    pub dbpath: Option<PathBuf>,
+    /// Go on without protecting the installed versions if pacman's local database can't be read
+    #[arg(long)]
+    pub allow_missing_db: bool,
+    /// Leave alone every version of the packages matching PKG, in addition to `IgnorePkg` with
+    /// `--config`
+    #[arg(long, value_name = "PKG", value_delimiter = ',')]
+    pub ignore: Vec<String>,
+    /// Leave alone every version of the installed packages of the groups matching GRP, in
+    /// addition to `IgnoreGroup` with `--config`
+    #[arg(long, value_name = "GRP", value_delimiter = ',')]
+    pub ignoregroup: Vec<String>,
+    /// Read the name and version of each package from the `.PKGINFO` inside its archive
+    #[cfg(feature = "pkginfo")]
+    #[arg(long)]
+    pub pkginfo: bool,
+}
+
+/// How much is asked before removing anything.
+#[derive(Debug, Args)]
+pub struct PromptArgs {
+    /// Don't remove nor change any file
+    #[arg(short, long)]
+    pub dry_run: bool,
+    /// How often confirmation is asked
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        default_value = "ambiguities",
+        value_parser = parser::<AutoConfirmLevel>(&["nothing", "removal", "ambiguities", "everything"]),
+        overrides_with_all = ["nothing", "removal", "ambiguities", "everything"]
+    )]
+    pub confirm_level: AutoConfirmLevel,
+    /// Same as `--confirm-level nothing` : ask nothing, ambiguities are resolved with the most
+    /// recently built version
+    #[arg(short = '0', overrides_with_all = ["confirm_level", "removal", "ambiguities", "everything"])]
+    pub nothing: bool,
+    /// Same as `--confirm-level removal` : ask only before removing anything
+    #[arg(short = '1', overrides_with_all = ["confirm_level", "nothing", "ambiguities", "everything"])]
+    pub removal: bool,
+    /// Same as `--confirm-level ambiguities` : ask about the ambiguous versions and before
+    /// removing anything
+    #[arg(short = '2', overrides_with_all = ["confirm_level", "nothing", "removal", "everything"])]
+    pub ambiguities: bool,
+    /// Same as `--confirm-level everything` : ask about every package
+    #[arg(short = '3', overrides_with_all = ["confirm_level", "nothing", "removal", "ambiguities"])]
+    pub everything: bool,
+    /// Abort instead of waiting for an answer when stdin isn't a terminal
+    #[arg(long)]
+    pub non_interactive: bool,
+    /// Date shown next to each version when asking about ambiguities
+    #[arg(
+        long,
+        value_name = "TIME",
+        default_value = "created",
+        value_parser = parser::<DisplayedTime>(&["created", "modified", "built"])
+    )]
+    pub time: DisplayedTime,
+}
+
+/// Where the old packages go.
+#[derive(Debug, Args)]
+pub struct RemovalArgs {
+    /// Move the old packages to the quarantine directory DIR instead of removing them
+    #[arg(long, value_name = "DIR")]
+    pub move_to: Option<PathBuf>,
+    /// Move the old packages to the trash instead of removing them
+    #[arg(long, conflicts_with = "move_to")]
+    pub trash: bool,
+    /// Where the journals are written, `$XDG_STATE_HOME/remove_old_arch_pkgs` by default
+    #[arg(long, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+}
+
+/// How the decisions are printed.
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// Print the decisions as one JSON document on stdout, everything else going to stderr
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "text",
+        value_parser = parser::<OutputFormat>(&["text", "json"])
+    )]
+    pub format: OutputFormat,
+    /// Print why each file is kept, removed or ambiguous
+    #[arg(long)]
+    pub explain: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct CleanArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+    #[command(flatten)]
+    pub prompt: PromptArgs,
+    #[command(flatten)]
+    pub removal: RemovalArgs,
+    #[command(flatten)]
+    pub report: ReportArgs,
+    /// Save the plan to FILE instead of removing anything, to apply it later
+    #[arg(long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+    /// Write a shell script removing the old packages to FILE instead of removing anything
+    #[arg(long, value_name = "FILE", conflicts_with = "trash")]
+    pub emit_script: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ListArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+    #[command(flatten)]
+    pub report: ReportArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct PlanArgs {
+    #[command(flatten)]
+    pub scan: ScanArgs,
+    #[command(flatten)]
+    pub report: ReportArgs,
+    /// Where the plan is saved
+    #[arg(short, long, value_name = "FILE")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ApplyArgs {
+    /// Plan saved with `plan --output` or `clean --output`
+    pub plan: PathBuf,
+    /// Cache directories the plan may remove files from : the `CacheDir` entries with
+    /// `--config`, `/var/cache/pacman/pkg/` otherwise
+    pub dirs: Vec<PathBuf>,
+    /// Take the cache directories from pacman's configuration FILE, `/etc/pacman.conf` if not
+    /// given (the directories then go before `-c`, or after `--`)
+    #[arg(
+        short = 'c',
+        long,
+        value_name = "FILE",
+        num_args = 0..=1,
+        default_missing_value = DEFAULT_PACMAN_CONF
+    )]
+    pub config: Option<PathBuf>,
+    /// Leave alone the files that changed since the plan was made instead of refusing to
+    /// remove anything
+    #[arg(long)]
+    pub skip_changed: bool,
+    /// Pacman database directory, nothing is removed while pacman holds its lock
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_DBPATH)]
+    pub dbpath: PathBuf,
+    #[command(flatten)]
+    pub prompt: PromptArgs,
+    #[command(flatten)]
+    pub removal: RemovalArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreArgs {
+    /// Journal of the run to undo
+    pub journal: PathBuf,
+    /// Only list the files to restore
+    #[arg(short, long)]
+    pub dry_run: bool,
+    /// Pacman database directory, nothing is restored while pacman holds its lock
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_DBPATH)]
+    pub dbpath: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct PurgeArgs {
+    /// Quarantine directory given to `--move-to`
+    pub quarantine: PathBuf,
+    /// Remove the files quarantined for more than DAYS days
+    #[arg(long, value_name = "DAYS", value_parser = parse_days)]
+    pub older_than: u64,
+    /// Pacman database directory, nothing is removed while pacman holds its lock
+    #[arg(long, value_name = "DIR", default_value = DEFAULT_DBPATH)]
+    pub dbpath: PathBuf,
+    /// Where the journals are written, `$XDG_STATE_HOME/remove_old_arch_pkgs` by default
+    #[arg(long, value_name = "DIR")]
+    pub state_dir: Option<PathBuf>,
+    #[command(flatten)]
+    pub prompt: PromptArgs,
+}
+
+/// Accepts one of `values`, parsed with `T::from_str`.
+fn parser<T>(values: &'static [&'static str]) -> impl TypedValueParser<Value = T>
+where
+    T: std::str::FromStr + Clone + Send + Sync + 'static,
+    T::Err: std::fmt::Debug,
+{
+    PossibleValuesParser::new(values).map(|s| s.parse::<T>().unwrap())
+}
+
+/// Seconds in a day.
+pub const DAY: u64 = 24 * 60 * 60;
+
+/// Parses a number of days that fits in a [`Duration`](std::time::Duration) of seconds.
+fn parse_days(s: &str) -> Result<u64, String> {
+    let days: u64 = s.parse().map_err(|err| format!("{}", err))?;
+    days.checked_mul(DAY)
+        .map(|_| days)
+        .ok_or_else(|| format!("{} days is too long", days))
+}
+
+impl ScanArgs {
+    pub fn configure(&self, opts: &mut Options) {
+        if !self.dirs.is_empty() {
+            opts.dirs = self.dirs.clone();
+        }
+        opts.max_depth = match (self.recursive, self.max_depth) {
+            (_, Some(max_depth)) => Some(max_depth),
+            (true, None) => None,
+            (false, None) => Some(0),
+        };
+        opts.follow_symlinks = self.follow_symlinks;
+        opts.exclude = self.exclude.clone();
+        opts.group_by = self.group_by;
+        opts.keep_versions = self.keep;
+        opts.keep_uninstalled = self.keep_uninstalled;
+        opts.remove_duplicates = self.remove_duplicates;
+        opts.comparator = self.comparator;
+        if let Some(dbpath) = &self.dbpath {
+            opts.dbpath = dbpath.clone();
+        }
+        opts.allow_missing_db = self.allow_missing_db;
+        opts.ignore_pkgs.extend(self.ignore.iter().cloned());
+        opts.ignore_groups.extend(self.ignoregroup.iter().cloned());
+        #[cfg(feature = "pkginfo")]
+        {
+            opts.read_pkginfo = self.pkginfo;
+        }
+    }
+}
+
+impl PromptArgs {
+    pub fn configure(&self, opts: &mut Options) {
+        opts.dry_run = self.dry_run;
+        opts.non_interactive = self.non_interactive;
+        opts.displayed_time = self.time;
+        opts.auto_confirm_level = match (
+            self.nothing,
+            self.removal,
+            self.ambiguities,
+            self.everything,
+        ) {
+            (true, ..) => AutoConfirmLevel::Nothing,
+            (_, true, ..) => AutoConfirmLevel::Removal,
+            (_, _, true, _) => AutoConfirmLevel::Ambiguities,
+            (.., true) => AutoConfirmLevel::Everything,
+            _ => self.confirm_level,
+        };
+    }
+}
+
+impl RemovalArgs {
+    pub fn configure(&self, opts: &mut Options) {
+        opts.move_to = self.move_to.clone();
+        opts.trash = self.trash;
+        if let Some(state_dir) = &self.state_dir {
+            opts.state_dir = state_dir.clone();
+        }
+    }
+}
+
+impl ReportArgs {
+    pub fn configure(&self, opts: &mut Options) {
+        opts.format = self.format;
+        opts.explain = self.explain;
+    }
+}
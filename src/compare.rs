@@ -0,0 +1,86 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use version_compare::{Cmp, Version};
+
+use crate::vercmp::vercmp;
+
+/// Strategy used to order two versions of the same package.
+pub trait VersionComparator {
+    /// Short name of the backend, as accepted by [`ComparatorKind::from_str`].
+    fn name(&self) -> &'static str;
+
+    /// Compares two full versions (`[epoch:]pkgver-pkgrel`).
+    ///
+    /// Returns `None` when the versions are different but can't be ordered.
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering>;
+}
+
+/// Compares versions exactly like pacman (see [`vercmp`]), it can always order them.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AlpmComparator;
+
+impl VersionComparator for AlpmComparator {
+    fn name(&self) -> &'static str {
+        "alpm"
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        Some(vercmp(a, b))
+    }
+}
+
+/// Compares versions using the generic heuristics of the `version_compare` crate, which knows
+/// nothing about epochs nor pkgrels.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VersionCompareComparator;
+
+impl VersionComparator for VersionCompareComparator {
+    fn name(&self) -> &'static str {
+        "version-compare"
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Option<Ordering> {
+        match Version::compare(&Version::from(a)?, &Version::from(b)?) {
+            Cmp::Eq => Some(Ordering::Equal),
+            Cmp::Ge | Cmp::Gt => Some(Ordering::Greater),
+            Cmp::Le | Cmp::Lt => Some(Ordering::Less),
+            Cmp::Ne => None,
+        }
+    }
+}
+
+/// Which [`VersionComparator`] to use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ComparatorKind {
+    /// [`AlpmComparator`]
+    #[default]
+    Alpm,
+    /// [`VersionCompareComparator`]
+    VersionCompare,
+}
+
+impl FromStr for ComparatorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alpm" => Ok(ComparatorKind::Alpm),
+            "version-compare" => Ok(ComparatorKind::VersionCompare),
+            _ => Err(format!(
+                "unknown comparator `{}`, expected `alpm` or `version-compare`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ComparatorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ComparatorKind::Alpm => write!(f, "{}", AlpmComparator.name()),
+            ComparatorKind::VersionCompare => write!(f, "{}", VersionCompareComparator.name()),
+        }
+    }
+}
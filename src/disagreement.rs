@@ -0,0 +1,71 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::compare::VersionComparator;
+use crate::package::Package;
+
+/// Two versions of the same package that two backends don't order the same way.
+#[derive(Debug, Clone)]
+pub struct Disagreement {
+    pub name: String,
+    pub version_a: String,
+    pub version_b: String,
+    /// How the first backend orders `version_a` against `version_b`, `None` if it can't.
+    pub left: Option<Ordering>,
+    /// How the second backend orders `version_a` against `version_b`, `None` if it can't.
+    pub right: Option<Ordering>,
+}
+
+/// `<`, `=`, `>` or `?` when the versions couldn't be ordered.
+pub fn ordering_symbol(ord: Option<Ordering>) -> &'static str {
+    match ord {
+        Some(Ordering::Less) => "<",
+        Some(Ordering::Equal) => "=",
+        Some(Ordering::Greater) => ">",
+        None => "?",
+    }
+}
+
+/// Runs every pair of versions of each package through both comparators and returns the pairs
+/// they disagree on, sorted by package name then versions.
+pub(crate) fn find_disagreements<L: VersionComparator, R: VersionComparator>(
+    pkgs: &[Package],
+    left: &L,
+    right: &R,
+) -> Vec<Disagreement> {
+    let mut by_name: HashMap<&str, Vec<&str>> = HashMap::new();
+    for pkg in pkgs {
+        let versions = by_name.entry(&pkg.name).or_default();
+        // Same version with another arch or compression algorithm, nothing new to compare.
+        if !versions.contains(&&pkg.pkgver[..]) {
+            versions.push(&pkg.pkgver);
+        }
+    }
+
+    let mut disagreements = Vec::new();
+    for (name, mut versions) in by_name {
+        versions.sort_unstable();
+        for (i, a) in versions.iter().enumerate() {
+            for b in versions[(i + 1)..].iter() {
+                let (l, r) = (left.compare(a, b), right.compare(a, b));
+                if l != r {
+                    disagreements.push(Disagreement {
+                        name: name.to_string(),
+                        version_a: a.to_string(),
+                        version_b: b.to_string(),
+                        left: l,
+                        right: r,
+                    });
+                }
+            }
+        }
+    }
+
+    disagreements.sort_by(|a, b| {
+        a.name
+            .cmp(&b.name)
+            .then_with(|| a.version_a.cmp(&b.version_a))
+            .then_with(|| a.version_b.cmp(&b.version_b))
+    });
+    disagreements
+}
@@ -0,0 +1,83 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::package::PackageParseError;
+use crate::prompt::NoAnswer;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Input-output error on `path`
+    Io { path: PathBuf, source: io::Error },
+    /// `path` isn't a package we understand
+    Parse {
+        path: PathBuf,
+        kind: PackageParseError,
+    },
+    /// The user didn't answer, nothing has been removed
+    Aborted(NoAnswer),
+    /// Pacman is running : its database lock file exists
+    Locked(PathBuf),
+    /// Files of a saved plan are gone or changed since it was made, nothing has been removed
+    Stale(Vec<(PathBuf, String)>),
+    /// Some files couldn't be removed (or moved, or restored), the others have been anyway
+    PartialRemoval {
+        removed: Vec<PathBuf>,
+        failed: Vec<(PathBuf, io::Error)>,
+    },
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => write!(f, "`{}` : {}", path.display(), source),
+            Error::Parse { path, kind } => write!(f, "`{}` : {}", path.display(), kind),
+            Error::Aborted(no_answer) => write!(f, "aborted, {}", no_answer),
+            Error::Locked(lock) => write!(
+                f,
+                "pacman seems to be running (`{}` exists), try again once it's done",
+                lock.display()
+            ),
+            Error::Stale(changed) => write!(
+                f,
+                "{} files changed since the plan was made, make a new one",
+                changed.len()
+            ),
+            Error::PartialRemoval { removed, failed } => write!(
+                f,
+                "{} files failed ({} succeeded)",
+                failed.len(),
+                removed.len()
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Parse { kind, .. } => Some(kind),
+            Error::Aborted(no_answer) => Some(no_answer),
+            Error::Locked(_) | Error::Stale(_) | Error::PartialRemoval { .. } => None,
+        }
+    }
+}
+
+impl From<NoAnswer> for Error {
+    fn from(no_answer: NoAnswer) -> Self {
+        Error::Aborted(no_answer)
+    }
+}
@@ -0,0 +1,139 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::compare::VersionComparator;
+use crate::disagreement::ordering_symbol;
+use crate::package::Package;
+
+/// Why the versions of a package are kept, removed or ambiguous.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub name: String,
+    /// Every distinct version found
+    pub versions: Vec<String>,
+    /// Every pair of distinct versions, `None` meaning the comparator can't order them
+    pub comparisons: Vec<(String, String, Option<Ordering>)>,
+    /// The decision for each package file, with the signature paired with it
+    pub files: Vec<FileDecision>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileDecision {
+    pub path: PathBuf,
+    pub version: String,
+    /// Its signature, which goes wherever the package goes
+    pub sig: Option<PathBuf>,
+    /// Its identical copies in the following directories, which go wherever it goes too
+    pub duplicates: Vec<PathBuf>,
+    pub rule: Rule,
+}
+
+/// What decided the fate of a package file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Kept as one of the `keep` newest versions
+    Newest { keep: usize, uninstalled: bool },
+    /// Kept because it is the installed version
+    Installed,
+    /// Removed as older than the `keep` newest versions
+    Older { keep: usize, uninstalled: bool },
+    /// Can't be ordered against the other versions around the limit, `slots` of them are kept
+    Ambiguous { slots: usize },
+    /// Removed as an identical copy of a kept file of a previous directory, see
+    /// [`Options::remove_duplicates`](crate::Options::remove_duplicates)
+    Duplicate,
+}
+
+impl Explanation {
+    /// Compares every pair of distinct versions of `name` with `cmp`.
+    pub(crate) fn new<C: VersionComparator>(cmp: &C, name: &str, versions: &[&Package]) -> Self {
+        let mut found: Vec<String> = Vec::new();
+        for p in versions {
+            if !found.contains(&p.pkgver) {
+                found.push(p.pkgver.clone());
+            }
+        }
+
+        let mut comparisons = Vec::new();
+        for (i, a) in found.iter().enumerate() {
+            for b in found[(i + 1)..].iter() {
+                comparisons.push((a.clone(), b.clone(), cmp.compare(a, b)));
+            }
+        }
+
+        Explanation {
+            name: name.to_string(),
+            versions: found,
+            comparisons,
+            files: Vec::new(),
+        }
+    }
+
+    pub(crate) fn decide(&mut self, pkg: &Package, rule: Rule) {
+        self.files.push(FileDecision {
+            path: pkg.path.clone(),
+            version: pkg.pkgver.clone(),
+            sig: pkg.sig.clone(),
+            duplicates: pkg.duplicates.iter().map(|d| d.path.clone()).collect(),
+            rule,
+        });
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let newest = |keep: usize, uninstalled: bool| {
+            format!(
+                "{}{}",
+                if keep == 1 {
+                    "the newest".to_string()
+                } else {
+                    format!("the {} newest", keep)
+                },
+                if uninstalled { " (uninstalled)" } else { "" }
+            )
+        };
+        match *self {
+            Rule::Newest {
+                keep: 1,
+                uninstalled,
+            } => {
+                write!(f, "kept, {}", newest(1, uninstalled))
+            }
+            Rule::Newest { keep, uninstalled } => {
+                write!(f, "kept, among {}", newest(keep, uninstalled))
+            }
+            Rule::Installed => write!(f, "kept, installed version"),
+            Rule::Older { keep, uninstalled } => {
+                write!(f, "removed, older than {}", newest(keep, uninstalled))
+            }
+            Rule::Ambiguous { slots } => write!(
+                f,
+                "ambiguous, can't be ordered around the limit ({} to keep)",
+                slots
+            ),
+            Rule::Duplicate => write!(f, "removed, identical copy of a kept file"),
+        }
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} : {}", self.name, self.versions.join(", "))?;
+        for (a, b, ord) in self.comparisons.iter() {
+            writeln!(f, "\t`{}` {} `{}`", a, ordering_symbol(*ord), b)?;
+        }
+        for d in self.files.iter() {
+            writeln!(f, "\t{}\t({})", d.path.display(), d.rule)?;
+            match &d.sig {
+                Some(sig) => writeln!(f, "\t\twith signature {}", sig.display())?,
+                None => writeln!(f, "\t\twithout signature")?,
+            }
+            for duplicate in d.duplicates.iter() {
+                writeln!(f, "\t\tidentical copy {}", duplicate.display())?;
+            }
+        }
+        Ok(())
+    }
+}
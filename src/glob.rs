@@ -0,0 +1,115 @@
+/// Whether `name` matches the shell pattern `pattern` (`*`, `?`, `[abc]`, `[a-z]`, `[!abc]`),
+/// the way pacman matches `IgnorePkg` and `Include`.
+pub(crate) fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+/// Whether `pattern` has any wildcard.
+pub(crate) fn is_pattern(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+fn matches(p: &[char], n: &[char]) -> bool {
+    match p.first() {
+        None => n.is_empty(),
+        Some('*') => (0..=n.len()).any(|i| matches(&p[1..], &n[i..])),
+        Some('?') => !n.is_empty() && matches(&p[1..], &n[1..]),
+        Some('[') => match (class_end(p), n.first()) {
+            (Some(end), Some(&c)) => {
+                class_matches(&p[1..end], c) && matches(&p[(end + 1)..], &n[1..])
+            }
+            (Some(_), None) => false,
+            // No closing `]`, it is a plain `[`.
+            (None, first) => first == Some(&'[') && matches(&p[1..], &n[1..]),
+        },
+        Some(c) => n.first() == Some(c) && matches(&p[1..], &n[1..]),
+    }
+}
+
+/// Index of the `]` closing the class opened at the start of `p`.
+fn class_end(p: &[char]) -> Option<usize> {
+    let start = if p.get(1) == Some(&'!') { 2 } else { 1 };
+    // A `]` right after the opening is part of the class.
+    p.iter()
+        .skip(start + 1)
+        .position(|&c| c == ']')
+        .map(|i| i + start + 1)
+}
+
+fn class_matches(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.first() {
+        Some('!') => (true, &class[1..]),
+        _ => (false, class),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcards() {
+        assert!(glob_match("linux*", "linux-headers"));
+        assert!(glob_match("linux*", "linux"));
+        assert!(!glob_match("linux*", "lib32-linux"));
+        assert!(glob_match("*.conf", "nas.conf"));
+        assert!(!glob_match("*.conf", "nas.conf.bak"));
+        assert!(glob_match("python?", "python3"));
+        assert!(!glob_match("python?", "python"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(glob_match("lib[abc]", "libb"));
+        assert!(!glob_match("lib[abc]", "libd"));
+        assert!(!glob_match("lib[abc]", "lib"));
+        assert!(glob_match("[]x]", "]"));
+    }
+
+    #[test]
+    fn negated_classes() {
+        assert!(glob_match("lib[!abc]", "libd"));
+        assert!(!glob_match("lib[!abc]", "liba"));
+        assert!(!glob_match("lib[!abc]", "lib"));
+        assert!(glob_match("[!]]", "x"));
+        assert!(!glob_match("[!]]", "]"));
+    }
+
+    #[test]
+    fn ranges() {
+        assert!(glob_match("gcc[0-9]*", "gcc14-libs"));
+        assert!(!glob_match("gcc[0-9]*", "gcc-libs"));
+        assert!(glob_match("[a-cx]", "b"));
+        assert!(glob_match("[a-cx]", "x"));
+        assert!(!glob_match("[a-cx]", "d"));
+        assert!(glob_match("[!0-9]*", "qt6-base"));
+        assert!(!glob_match("[!0-9]*", "0ad"));
+        // A trailing `-` is a plain character.
+        assert!(glob_match("[a-]", "-"));
+    }
+
+    #[test]
+    fn unclosed_class() {
+        assert!(glob_match("foo[", "foo["));
+        assert!(!glob_match("foo[", "foo"));
+        assert!(glob_match("[ab*", "[abc"));
+        assert!(!glob_match("[ab", "a"));
+        assert!(is_pattern("foo["));
+        assert!(!is_pattern("foo.conf"));
+    }
+}
@@ -0,0 +1,251 @@
+use std::env::var_os;
+use std::ffi::OsStr;
+use std::fs::{create_dir_all, read_to_string, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{absolute, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use sha2::{Digest, Sha256};
+
+use crate::trash::{percent_decode, percent_encode};
+
+const HEADER: &str = "# remove_old_arch_pkgs journal";
+
+/// Record of a destructive run, to know what has been removed and to put quarantined files
+/// back.
+///
+/// It is written as `key = value` lines, a `moved` line following the `file` it applies to, the
+/// paths being percent-encoded as in a `.trashinfo` file :
+/// ```text
+/// # remove_old_arch_pkgs journal
+/// timestamp = 1700000000
+/// options = Options { ... }
+/// file = {sha256} {size} {path}
+/// moved = {path in quarantine}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Journal {
+    /// Unix timestamp of the run
+    pub timestamp: u64,
+    /// The options of the run, as debug output
+    pub options: String,
+    pub entries: Vec<JournalEntry>,
+}
+
+/// A file removed or moved by a run.
+#[derive(Debug, Clone)]
+pub struct JournalEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Hex digest of the content
+    pub sha256: String,
+    /// Where it has been moved, `None` if it has been removed for good
+    pub moved_to: Option<PathBuf>,
+}
+
+impl Journal {
+    /// Starts a journal of the run with the given `options`, dated now.
+    pub fn new(options: String, entries: Vec<JournalEntry>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        Journal {
+            timestamp,
+            options,
+            entries,
+        }
+    }
+
+    /// Writes the journal in `state_dir` (created if needed) and returns its path.
+    pub fn write(&self, state_dir: &Path) -> io::Result<PathBuf> {
+        create_dir_all(state_dir)?;
+        let (path, file) = create_unique(state_dir, self.timestamp)?;
+        let mut file = io::BufWriter::new(file);
+
+        writeln!(file, "{}", HEADER)?;
+        writeln!(file, "timestamp = {}", self.timestamp)?;
+        writeln!(file, "options = {}", self.options)?;
+        for entry in self.entries.iter() {
+            writeln!(
+                file,
+                "file = {} {} {}",
+                entry.sha256,
+                entry.size,
+                encode(&entry.path)
+            )?;
+            if let Some(moved_to) = &entry.moved_to {
+                writeln!(file, "moved = {}", encode(moved_to))?;
+            }
+        }
+        file.flush()?;
+
+        Ok(path)
+    }
+
+    /// Reads a journal written by [`Journal::write`].
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let path_of = |text: &str| decode(text).ok_or_else(|| invalid("bad path"));
+
+        let content = read_to_string(path)?;
+        let mut lines = content.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("not a journal"));
+        }
+
+        let mut journal = Journal {
+            timestamp: 0,
+            options: String::new(),
+            entries: Vec::new(),
+        };
+        for line in lines {
+            let (key, value) = line
+                .split_once(" = ")
+                .ok_or_else(|| invalid("line without ` = `"))?;
+            match key {
+                "timestamp" => {
+                    journal.timestamp = value.parse().map_err(|_| invalid("bad timestamp"))?
+                }
+                "options" => journal.options = value.to_string(),
+                "file" => {
+                    let mut fields = value.splitn(3, ' ');
+                    let (sha256, size, path) = match (fields.next(), fields.next(), fields.next()) {
+                        (Some(sha256), Some(size), Some(path)) => (sha256, size, path),
+                        _ => return Err(invalid("bad file line")),
+                    };
+                    journal.entries.push(JournalEntry {
+                        path: path_of(path)?,
+                        size: size.parse().map_err(|_| invalid("bad file size"))?,
+                        sha256: sha256.to_string(),
+                        moved_to: None,
+                    });
+                }
+                "moved" => {
+                    journal
+                        .entries
+                        .last_mut()
+                        .ok_or_else(|| invalid("`moved` before any `file`"))?
+                        .moved_to = Some(path_of(value)?)
+                }
+                _ => (),
+            }
+        }
+
+        Ok(journal)
+    }
+}
+
+impl JournalEntry {
+    /// Records `path` as it is now, before it is removed or moved to `moved_to`.
+    ///
+    /// Both paths are made absolute, so that the journal can be restored from any directory.
+    pub fn new(path: &Path, moved_to: Option<PathBuf>) -> io::Result<Self> {
+        let (size, sha256) = digest(path)?;
+        Ok(JournalEntry {
+            path: absolute(path)?,
+            size,
+            sha256,
+            moved_to: moved_to.as_deref().map(absolute).transpose()?,
+        })
+    }
+}
+
+fn encode(path: &Path) -> String {
+    percent_encode(path.as_os_str().as_bytes())
+}
+
+fn decode(path: &str) -> Option<PathBuf> {
+    percent_decode(path).map(|bytes| PathBuf::from(OsStr::from_bytes(&bytes)))
+}
+
+/// Default directory of the journals : `$XDG_STATE_HOME/remove_old_arch_pkgs`, falling back to
+/// `~/.local/state/remove_old_arch_pkgs`, or `/var/lib/remove_old_arch_pkgs` without a home.
+pub fn default_state_dir() -> PathBuf {
+    let base = match (var_os("XDG_STATE_HOME"), var_os("HOME")) {
+        (Some(state), _) if !state.is_empty() => PathBuf::from(state),
+        (_, Some(home)) if !home.is_empty() => Path::new(&home).join(".local/state"),
+        _ => PathBuf::from("/var/lib"),
+    };
+    base.join("remove_old_arch_pkgs")
+}
+
+/// Size and hex SHA-256 digest of the file at `path`.
+pub fn digest(path: &Path) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    Ok((size, sha256))
+}
+
+/// Creates `journal-{timestamp}.txt` in `dir`, with a suffix if several runs happen in the same
+/// second.
+fn create_unique(dir: &Path, timestamp: u64) -> io::Result<(PathBuf, File)> {
+    let mut n = 0;
+    loop {
+        let path = if n == 0 {
+            dir.join(format!("journal-{}.txt", timestamp))
+        } else {
+            dir.join(format!("journal-{}-{}.txt", timestamp, n))
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::remove_dir_all;
+    use std::process;
+
+    #[test]
+    fn write_read() {
+        let dir =
+            std::env::temp_dir().join(format!("remove_old_arch_pkgs-journal-{}", process::id()));
+        let entry = |path: &[u8], moved_to: Option<&[u8]>| JournalEntry {
+            path: PathBuf::from(OsStr::from_bytes(path)),
+            size: 42,
+            sha256: "0".repeat(64),
+            moved_to: moved_to.map(|p| PathBuf::from(OsStr::from_bytes(p))),
+        };
+        let journal = Journal::new(
+            "Options { .. }".to_string(),
+            vec![
+                entry(b"/cache/foo-1-1-x86_64.pkg.tar.zst", None),
+                entry(
+                    b"/cache/foo 1\nmoved = /etc/passwd-1.pkg.tar.zst",
+                    Some(b"/quarantine/foo 1\nmoved = /etc/passwd-1.pkg.tar.zst"),
+                ),
+                entry(
+                    b"/cache/caf\xc3\xa9%41-\xff.pkg.tar.zst",
+                    Some(b"/quarantine/x"),
+                ),
+            ],
+        );
+
+        let path = journal.write(&dir).unwrap();
+        let read = Journal::read(&path);
+        remove_dir_all(&dir).unwrap();
+        let read = read.unwrap();
+
+        assert_eq!(read.timestamp, journal.timestamp);
+        assert_eq!(read.options, journal.options);
+        assert_eq!(read.entries.len(), journal.entries.len());
+        for (read, written) in read.entries.iter().zip(journal.entries.iter()) {
+            assert_eq!(read.path, written.path);
+            assert_eq!(read.size, written.size);
+            assert_eq!(read.sha256, written.sha256);
+            assert_eq!(read.moved_to, written.moved_to);
+        }
+    }
+}
@@ -4,303 +4,897 @@ extern crate chrono;
 extern crate regex;
 extern crate version_compare;
 
-use std::cmp::Ordering;
-use std::collections::HashMap;
-#[cfg(feature = "chrono")]
-use std::fs::metadata;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::fs::read_dir;
-use std::fs::remove_file;
+use std::fs::{create_dir_all, remove_file, File};
 use std::io;
-use std::path::{Path, PathBuf};
-use std::str::FromStr;
+use std::path::{absolute, Path, PathBuf};
+use std::time::Duration;
 
+mod compare;
+pub use compare::{AlpmComparator, ComparatorKind, VersionComparator, VersionCompareComparator};
+mod disagreement;
+use disagreement::find_disagreements;
+pub use disagreement::ordering_symbol;
+pub use disagreement::Disagreement;
+mod error;
+pub use error::{Error, Result};
+mod explain;
+pub use explain::{Explanation, FileDecision, Rule};
+mod glob;
+use glob::glob_match;
+mod journal;
+pub use journal::{Journal, JournalEntry};
+mod localdb;
+pub use localdb::{InstalledPackage, LocalDb, DEFAULT_DBPATH};
 mod package;
-use package::{Package, Packages};
+use package::Packages;
+pub use package::{Package, PackageParseError};
+#[cfg(feature = "pkginfo")]
+mod pkginfo;
+#[cfg(feature = "pkginfo")]
+pub use pkginfo::PkgInfo;
 mod options;
-pub use options::{AutoConfirmLevel, Options};
+pub use options::{
+    AutoConfirmLevel, DisplayedTime, FollowSymlinks, Grouping, Options, OutputFormat,
+};
+mod pacman_conf;
+pub use pacman_conf::{PacmanConf, DEFAULT_CACHEDIR, DEFAULT_PACMAN_CONF};
+mod planfile;
+pub use planfile::{PlanFile, PlannedFile};
+mod plan;
+pub use plan::{Ambiguity, CleanupPlan, IgnoreReason, IgnoredFile};
+mod quarantine;
+use quarantine::{expired_files, move_file, quarantine_path};
+mod script;
+mod trash;
+pub use trash::Trash;
+mod prompt;
+pub use prompt::{NoAnswer, PolicyPrompter, Prompter, ScriptedPrompter, TerminalPrompter};
+mod vercmp;
+pub use vercmp::vercmp;
 
-// TODO: loglevel
-const DEBUG_VERSIONS_COMPARISON: bool = false;
+/// Held by pacman in its `DBPath` while it runs.
+const DB_LOCK_FILE: &str = "db.lck";
 
 const _TEST_NAME: &str = "/mnt/archlinux/linux-5.3.arch1-1-x86_64.pkg.tar.xz";
 const _TEST_NAME_2: &str = "/mnt/archlinux/linux-5.3.1.arch1-1-x86_64.pkg.tar.xz";
 const _TEST_NAME_3: &str = "/mnt/archlinux/zeitgeist-1.0+1+g1bcc8585-1-x86_64.pkg.tar.xz";
 
-pub fn remove_old_archlinux_packages(opts: Options) -> io::Result<()> {
-    let (old_pkgs, ignored_files) = list_old_archlinux_packages(&opts)?;
-
-    list_removed_files(&old_pkgs);
-    list_ignored_files(&ignored_files);
-    if !opts.dry_run {
-        let input = if opts.auto_confirm_level.is_at_least_removal() && !old_pkgs.is_empty() {
-            println!("\n------------");
-            println!("Are you agreeing to these removals ? Type `y` and press enter if you do.");
-            let mut input = String::new();
-            if let Err(err) = io::stdin().read_line(&mut input) {
-                // TODO: not panic ? proper error about unavailable stdin...
-                panic!(
-                    "EEE Can't read from input to ask anything to the user: {}",
-                    err
-                );
+/// Computes what cleaning `opts.dirs` would do, without removing nor asking anything.
+///
+/// The packages of all the directories are seen as one cache : only the newest versions among
+/// all of them are kept, and identical copies of a file in several directories are handled as
+/// one package (see [`Package::duplicates`]).
+///
+/// `opts.dirs` should be paths to existing **directories**, but we check that in main already.
+pub fn plan(opts: &Options) -> Result<CleanupPlan> {
+    match opts.comparator {
+        ComparatorKind::Alpm => plan_with(opts, &AlpmComparator),
+        ComparatorKind::VersionCompare => plan_with(opts, &VersionCompareComparator),
+    }
+}
+
+/// Same as [`plan`], comparing versions with `cmp` instead of `opts.comparator`.
+pub fn plan_with<C: VersionComparator>(opts: &Options, cmp: &C) -> Result<CleanupPlan> {
+    let mut plan = CleanupPlan {
+        dirs: opts.dirs.clone(),
+        ..Default::default()
+    };
+
+    let entry_paths = list_all_files(opts)?;
+    let (pkgs, ignored_files) = parse_packages(&entry_paths, opts.read_pkginfo);
+    let pkgs = merge_duplicates(pkgs, opts.group_by);
+    plan.ignored = ignored_files;
+
+    let local_db = match LocalDb::read(&opts.dbpath) {
+        // Without the installed packages, every cached package would be seen as uninstalled.
+        Ok(local_db) if local_db.is_empty() && opts.keep_uninstalled.is_some() => {
+            return Err(Error::io(
+                &opts.dbpath,
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "pacman local database is empty, can't tell which packages are uninstalled",
+                ),
+            ));
+        }
+        Ok(local_db) => local_db,
+        Err(err) if opts.keep_uninstalled.is_some() => {
+            return Err(Error::io(
+                &opts.dbpath,
+                io::Error::new(
+                    err.kind(),
+                    format!(
+                        "can't read pacman local database to find uninstalled packages : {}",
+                        err
+                    ),
+                ),
+            ));
+        }
+        Err(err) if opts.allow_missing_db => {
+            eprintln!(
+                "WWW Can't read pacman local database in `{}`, installed versions won't be protected : {}",
+                opts.dbpath.display(),
+                err
+            );
+            LocalDb::default()
+        }
+        // Without the installed packages, their archives could be removed.
+        Err(err) => {
+            return Err(Error::io(
+                &opts.dbpath,
+                io::Error::new(
+                    err.kind(),
+                    format!(
+                        "can't read pacman local database to protect the installed versions : {}",
+                        err
+                    ),
+                ),
+            ));
+        }
+    };
+
+    // (name, directory with `Grouping::PerDirectory`) => versions
+    let mut versions_by_name: HashMap<(String, Option<PathBuf>), Vec<Package>> = HashMap::new();
+    for pkg in pkgs {
+        versions_by_name
+            .entry((pkg.name.clone(), opts.group_by.group_of(&pkg.path)))
+            .or_default()
+            .push(pkg);
+    }
+
+    for ((name, _), versions) in versions_by_name {
+        // Pacman doesn't upgrade them, their versions are left alone.
+        if let Some(reason) = ignored_by_pacman(opts, &local_db, &name) {
+            for file in versions.iter().flat_map(Package::files) {
+                plan.ignored.push(IgnoredFile {
+                    path: file.to_path_buf(),
+                    reason: reason.clone(),
+                });
             }
+            continue;
+        }
 
-            input == "y\n"
-        } else {
-            true
+        let (keep_versions, uninstalled) = match opts.keep_uninstalled {
+            Some(keep_uninstalled) if local_db.get(&name).is_none() => (keep_uninstalled, true),
+            _ => (opts.keep_versions, false),
         };
+        let mut explanation = opts
+            .explain
+            .then(|| Explanation::new(cmp, &name, &versions.iter().collect::<Vec<_>>()));
 
-        if input {
-            remove_files(old_pkgs)?;
+        let mut p = Packages::split(
+            cmp,
+            versions,
+            keep_versions,
+            opts.auto_confirm_level.is_everything(),
+        );
+        let newest: Vec<PathBuf> = p.keep.iter().map(|p| p.path.clone()).collect();
+        p.keep_installed(|p| local_db.is_installed(&p.name, &p.pkgver));
+        let duplicates: Vec<Package> = if opts.remove_duplicates {
+            p.keep
+                .iter_mut()
+                .flat_map(|p| p.duplicates.drain(..))
+                .collect()
+        } else {
+            Vec::new()
+        };
 
-            // It has'n been shown before
-            if !opts.auto_confirm_level.is_at_least_removal() {
-                list_ignored_files(&ignored_files);
+        if let Some(explanation) = explanation.as_mut() {
+            for pkg in p.keep.iter() {
+                let rule = if newest.contains(&pkg.path) {
+                    Rule::Newest {
+                        keep: keep_versions,
+                        uninstalled,
+                    }
+                } else {
+                    Rule::Installed
+                };
+                explanation.decide(pkg, rule);
             }
-        } else {
-            println!("\n------------");
-            println!("Abording : Not removing any file.");
+            for pkg in p.ambs.iter() {
+                explanation.decide(pkg, Rule::Ambiguous { slots: p.slots });
+            }
+            for pkg in p.old.iter() {
+                explanation.decide(
+                    pkg,
+                    Rule::Older {
+                        keep: keep_versions,
+                        uninstalled,
+                    },
+                );
+            }
+            for pkg in duplicates.iter() {
+                explanation.decide(pkg, Rule::Duplicate);
+            }
+        }
+        plan.explanations.extend(explanation);
+
+        plan.old.append(&mut p.old);
+        plan.old.extend(duplicates);
+        plan.kept.append(&mut p.keep);
+        if !p.ambs.is_empty() {
+            // We get the most recently built on top, then the "biggest" string.
+            p.ambs
+                .sort_by_cached_key(|p| Reverse((p.build_time(), p.pkgver.clone())));
+            plan.ambiguities.push(Ambiguity {
+                name,
+                versions: p.ambs,
+                slots: p.slots,
+            });
         }
     }
 
+    plan.sort();
+    Ok(plan)
+}
+
+/// Removes the old packages of `plan` along with their signatures, or moves them to
+/// `opts.move_to` if set, or to the trash if `opts.trash` is set.
+///
+/// Unresolved ambiguities and ignored files are left alone.
+/// Nothing is removed while pacman holds its database lock in `opts.dbpath`.
+/// The files are first recorded in a [`Journal`] written in `opts.state_dir`, whose path is
+/// returned (`None` if there was nothing to remove).
+/// A file that can't be removed doesn't stop the others from being removed, the failures are
+/// all reported at the end with [`Error::PartialRemoval`].
+pub fn apply(plan: &CleanupPlan, opts: &Options) -> Result<Option<PathBuf>> {
+    remove_files(&plan.files_to_remove(), opts)
+}
+
+/// Saves the files to remove of `plan` to `path`, to be reviewed then applied later with
+/// [`apply_saved_plan`].
+pub fn save_plan(plan: &CleanupPlan, opts: &Options, path: &Path) -> Result<()> {
+    PlanFile::new(plan, &opts.dirs)
+        .map_err(|err| Error::io(path, err))?
+        .write(path)
+        .map_err(|err| Error::io(path, err))
+}
+
+/// Writes a POSIX shell script to `path`, doing what [`apply`] would do with `rm` or `mv`,
+/// to run it later instead.
+pub fn save_script(plan: &CleanupPlan, opts: &Options, path: &Path) -> Result<()> {
+    File::create(path)
+        .map(io::BufWriter::new)
+        .and_then(|out| script::write_script(plan, opts, out))
+        .map_err(|err| Error::io(path, err))
+}
+
+/// Removes the files of `plan`, read from `path`, the same way [`apply`] does, as long as they
+/// are all inside `opts.dirs`.
+///
+/// `changed` are the files gone or changed since the plan was made, as found by
+/// [`PlanFile::changed_files`] : if there are some, nothing is removed and they are reported
+/// with [`Error::Stale`], unless `skip_changed` is set : then they are left alone with a
+/// warning and the others are removed.
+///
+/// Nothing is removed either if the plan holds files that aren't packages or signatures of
+/// `opts.dirs`, see [`PlanFile::check_files`].
+pub fn apply_saved_plan(
+    plan: &PlanFile,
+    path: &Path,
+    changed: &[(PathBuf, String)],
+    opts: &Options,
+    skip_changed: bool,
+) -> Result<Option<PathBuf>> {
+    plan.check_files(&opts.dirs)
+        .map_err(|err| Error::io(path, err))?;
+    if !changed.is_empty() && !skip_changed {
+        return Err(Error::Stale(changed.to_vec()));
+    }
+    for (path, change) in changed.iter() {
+        eprintln!(
+            "WWW `{}` changed since the plan was made ({}), skipping it.",
+            path.display(),
+            change
+        );
+    }
+
+    let files: Vec<&Path> = plan
+        .files
+        .iter()
+        .map(|f| f.path.as_path())
+        .filter(|path| !changed.iter().any(|(changed, _)| changed == path))
+        .collect();
+    remove_files(&files, opts)
+}
+
+/// Removes (or moves, according to `opts`) `files` after recording them in a journal.
+fn remove_files(files: &[&Path], opts: &Options) -> Result<Option<PathBuf>> {
+    check_unlocked(opts)?;
+
+    if files.is_empty() {
+        return Ok(None);
+    }
+    if let Some(quarantine) = &opts.move_to {
+        create_dir_all(quarantine).map_err(|err| Error::io(quarantine, err))?;
+    }
+    let (journal, entries, failed) = write_journal(files, opts)?;
+
+    remove_each(&entries, failed, |entry| match &entry.moved_to {
+        Some(dest) => move_file(&entry.path, dest).inspect_err(|_| {
+            // Releases the name reserved in the trash.
+            let _ = trash::forget(dest);
+        }),
+        None => remove_file(&entry.path),
+    })?;
+    Ok(Some(journal))
+}
+
+/// Lists the files that have been in the quarantine directory `dir` (see
+/// [`Options::move_to`]) for more than `max_age`, to give to [`purge`].
+pub fn plan_purge(dir: &Path, max_age: Duration) -> Result<Vec<PathBuf>> {
+    expired_files(dir, max_age).map_err(|err| Error::io(dir, err))
+}
+
+/// Removes quarantined `files` for good, the same way [`apply`] does without quarantine.
+///
+/// Nothing is removed while pacman holds its database lock in `opts.dbpath`.
+pub fn purge(files: &[PathBuf], opts: &Options) -> Result<Option<PathBuf>> {
+    check_unlocked(opts)?;
+    let files: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+    if files.is_empty() {
+        return Ok(None);
+    }
+    let opts = Options {
+        move_to: None,
+        trash: false,
+        ..opts.clone()
+    };
+    let (journal, entries, failed) = write_journal(&files, &opts)?;
+    remove_each(&entries, failed, |entry| remove_file(&entry.path))?;
+    Ok(Some(journal))
+}
+
+/// Puts the files moved to quarantine or to the trash by the run recorded in the journal at `journal` back
+/// where they were.
+///
+/// Files that have been removed for good, that have changed since, or whose original path is
+/// taken again are left alone and reported with [`Error::PartialRemoval`].
+/// Returns the restored files.
+pub fn restore(journal: &Path, opts: &Options) -> Result<Vec<PathBuf>> {
+    check_unlocked(opts)?;
+    let journal = Journal::read(journal).map_err(|err| Error::io(journal, err))?;
+
+    let mut restored = Vec::new();
+    let mut failed = Vec::new();
+    for entry in journal.entries.iter() {
+        match restore_entry(entry) {
+            Ok(()) => restored.push(entry.path.clone()),
+            Err(err) => failed.push((entry.path.clone(), err)),
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(restored)
+    } else {
+        Err(Error::PartialRemoval {
+            removed: restored,
+            failed,
+        })
+    }
+}
+
+/// Scans `opts.dirs` the same way [`plan`] does and returns every pair of versions that the
+/// `alpm` and `version-compare` backends don't order the same way.
+///
+/// Nothing is ever removed.
+pub fn compare_backends(opts: &Options) -> Result<Vec<Disagreement>> {
+    let entry_paths = list_all_files(opts)?;
+    let (pkgs, _) = parse_packages(&entry_paths, opts.read_pkginfo);
+    let pkgs = merge_duplicates(pkgs, opts.group_by);
+    Ok(find_disagreements(
+        &pkgs,
+        &AlpmComparator,
+        &VersionCompareComparator,
+    ))
+}
+
+/// Resolves each ambiguity of `plan` with the choice of `prompter`.
+///
+/// If `prompter` has no answer, the remaining ambiguities stay unresolved.
+pub fn resolve_ambiguities<P: Prompter>(
+    plan: &mut CleanupPlan,
+    prompter: &mut P,
+) -> std::result::Result<(), NoAnswer> {
+    let mut ambiguities = std::mem::take(&mut plan.ambiguities).into_iter();
+    while let Some(amb) = ambiguities.next() {
+        let choice = match prompter.choose_versions(&amb) {
+            Ok(choice) => choice,
+            Err(err) => {
+                plan.ambiguities.push(amb);
+                plan.ambiguities.extend(ambiguities);
+                return Err(err);
+            }
+        };
+        let choice = choice.filter(|choice| {
+            let valid = choice.iter().all(|&i| i < amb.versions.len());
+            if !valid {
+                eprintln!(
+                    "WWW invalid choice {:?} for package `{}`, leaving it alone.",
+                    choice, amb.name
+                );
+            }
+            valid
+        });
+        plan.resolve(amb, choice.as_deref());
+    }
     Ok(())
 }
 
-/// Returns a list of all archlinux packages in `dir` if there is a newer version
-/// also present.
-/// `dir` should be a path to an existing **directory**, but we check that in main already.
-/// Returns : `(old_pkgs, ignored_files)` where:
-///     - `old_pkgs` are the packages that have a newer version
-///     - `ignored_files` are the files ignored because of ambiguous version number or non-package
-fn list_old_archlinux_packages(opts: &Options) -> io::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
-    let mut old_pkgs = Vec::new();
-    let mut new_pkgs: HashMap<&str, Packages> = HashMap::new();
-    let mut ignored_files = Vec::new();
-    let mut sig_files = Vec::new();
+/// Plans the cleaning of `opts.dirs`, asks `prompter` for the ambiguities and the confirmation,
+/// then removes the old packages (unless it's a dry run).
+///
+/// Returns the resolved plan, nothing is removed if `prompter` has no answer.
+pub fn clean<P: Prompter>(opts: &Options, prompter: &mut P) -> Result<CleanupPlan> {
+    let mut plan = plan(opts)?;
+    resolve_ambiguities(&mut plan, prompter)?;
+    if !opts.dry_run && prompter.confirm_removal(&plan.files_to_remove())? {
+        apply(&plan, opts)?;
+    }
+    Ok(plan)
+}
+
+/// Why the package `name` is ignored by pacman according to `opts.ignore_pkgs` and
+/// `opts.ignore_groups`, if it is.
+///
+/// Only the groups of the installed packages are known.
+fn ignored_by_pacman(opts: &Options, local_db: &LocalDb, name: &str) -> Option<IgnoreReason> {
+    if opts.ignore_pkgs.iter().any(|p| glob_match(p, name)) {
+        return Some(IgnoreReason::IgnorePkg);
+    }
+    let groups = local_db.get(name).map_or(&[][..], |p| &p.groups[..]);
+    groups
+        .iter()
+        .find(|group| opts.ignore_groups.iter().any(|p| glob_match(p, group)))
+        .map(|group| IgnoreReason::IgnoreGroup(group.clone()))
+}
+
+fn check_unlocked(opts: &Options) -> Result<()> {
+    let lock = opts.dbpath.join(DB_LOCK_FILE);
+    if lock.exists() {
+        Err(Error::Locked(lock))
+    } else {
+        Ok(())
+    }
+}
+
+/// Files that couldn't be removed (or moved, or restored), with why.
+type Failures = Vec<(PathBuf, io::Error)>;
+
+/// Records `files` in a journal in `opts.state_dir` before they are removed or moved, and
+/// returns its path along with the entries, and the files that can't be recorded (gone since
+/// the plan was made, unreadable, ...) with why : they are left alone.
+///
+/// In trash mode, the names of the files are reserved in the trash at this point, and released
+/// if the journal can't be written.
+fn write_journal(
+    files: &[&Path],
+    opts: &Options,
+) -> Result<(PathBuf, Vec<JournalEntry>, Failures)> {
+    let mut entries = Vec::with_capacity(files.len());
+    let mut failed = Vec::new();
+    let mut taken = HashSet::new();
+    for &file in files {
+        // Hashed first, so that nothing is reserved for a file that can't be read.
+        let entry = JournalEntry::new(file, None).and_then(|entry| {
+            let moved_to = match &opts.move_to {
+                Some(quarantine) => {
+                    let dest = quarantine_path(file, quarantine, &taken)?;
+                    taken.insert(dest.clone());
+                    Some(dest)
+                }
+                None if opts.trash => Some(Trash::for_file(file)?.reserve(file)?),
+                None => None,
+            };
+            Ok(JournalEntry {
+                moved_to: moved_to.as_deref().map(absolute).transpose()?,
+                ..entry
+            })
+        });
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(err) => failed.push((file.to_path_buf(), err)),
+        }
+    }
+
+    let journal = Journal::new(format!("{:?}", opts), entries);
+    let path = journal.write(&opts.state_dir).map_err(|err| {
+        for dest in journal.entries.iter().filter_map(|e| e.moved_to.as_deref()) {
+            let _ = trash::forget(dest);
+        }
+        Error::io(&opts.state_dir, err)
+    })?;
+    Ok((path, journal.entries, failed))
+}
+
+/// Moves a quarantined (or trashed) file back to its original path, if it hasn't changed.
+fn restore_entry(entry: &JournalEntry) -> io::Result<()> {
+    let quarantined = entry.moved_to.as_deref().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            "removed for good, can't be restored",
+        )
+    })?;
+    if entry.path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "a file is there already",
+        ));
+    }
+    let (size, sha256) = journal::digest(quarantined)?;
+    if size != entry.size || sha256 != entry.sha256 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("`{}` has changed since", quarantined.display()),
+        ));
+    }
+
+    if let Some(parent) = entry.path.parent() {
+        create_dir_all(parent)?;
+    }
+    move_file(quarantined, &entry.path)?;
+    trash::forget(quarantined)
+}
+
+/// Calls `remove` on each journal entry, going on after failures, which are reported along with
+/// the ones already `failed`.
+fn remove_each<F>(entries: &[JournalEntry], mut failed: Failures, mut remove: F) -> Result<()>
+where
+    F: FnMut(&JournalEntry) -> io::Result<()>,
+{
+    let mut removed = Vec::new();
+    for entry in entries {
+        match remove(entry) {
+            Ok(()) => removed.push(entry.path.clone()),
+            Err(err) => failed.push((entry.path.clone(), err)),
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PartialRemoval { removed, failed })
+    }
+}
 
+/// Lists the files inside each of `opts.dirs`, in their order, and inside their subdirectories
+/// according to `opts.max_depth`, `opts.follow_symlinks` and `opts.exclude`.
+///
+/// A directory found twice (given twice, or through a symbolic link) is listed once.
+fn list_all_files(opts: &Options) -> Result<Vec<PathBuf>> {
     let mut entry_paths = Vec::new();
-    for entry in read_dir(&opts.dir)? {
-        let entry_path = entry?.path();
-        if !entry_path.is_file() || ignored_files.contains(&entry_path) {
-            continue;
+    let mut seen = HashSet::new();
+    for dir in opts.dirs.iter() {
+        let canonical = dir.canonicalize().map_err(|err| Error::io(dir, err))?;
+        if seen.insert(canonical) {
+            list_files(dir, dir, 0, opts, &mut seen, &mut entry_paths)?;
         }
-        entry_paths.push(entry_path);
     }
+    Ok(entry_paths)
+}
 
-    // TODO: extract the function(s)
-    // for entry in read_dir(&opts.dir)? {
-    for entry_path in entry_paths.iter() {
-        if entry_path.extension().map_or(false, |s| s == "sig") {
-            sig_files.push(entry_path);
+/// Lists the files inside `dir`, found `depth` levels below `root`, then goes down its
+/// subdirectories not `seen` yet.
+///
+/// The files of the subdirectories are only listed if they are named like packages or
+/// signatures : build directories hold many other files. A subdirectory that can't be read is
+/// skipped with a warning.
+fn list_files(
+    root: &Path,
+    dir: &Path,
+    depth: usize,
+    opts: &Options,
+    seen: &mut HashSet<PathBuf>,
+    entry_paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let mut subdirs = Vec::new();
+    for entry in read_dir(dir).map_err(|err| Error::io(dir, err))? {
+        let entry = entry.map_err(|err| Error::io(dir, err))?;
+        let entry_path = entry.path();
+        if is_excluded(root, &entry_path, &opts.exclude) {
             continue;
         }
 
-        let pkg = match Package::from_path(entry_path) {
-            Ok(pkg) => pkg,
-            Err((e, entry_path)) => {
-                println!("{} {:?}", entry_path.display(), e);
-                ignored_files.push(entry_path);
+        let is_symlink = entry.file_type().is_ok_and(|t| t.is_symlink());
+        if entry_path.is_file() {
+            if depth == 0 || is_package_name(&entry_path) {
+                entry_paths.push(entry_path);
+            }
+        } else if entry_path.is_dir()
+            && (!is_symlink || opts.follow_symlinks == FollowSymlinks::Always)
+            && opts.max_depth.is_none_or(|max| depth < max)
+        {
+            subdirs.push(entry_path);
+        }
+    }
+
+    subdirs.sort();
+    for subdir in subdirs {
+        let canonical = match subdir.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(err) => {
+                eprintln!("WWW Can't scan `{}` : {}", subdir.display(), err);
                 continue;
             }
         };
-
-        if let Some(existing_pkg) = new_pkgs.get_mut(&pkg.name) {
-            if pkg.path == existing_pkg.path {
-                panic!("Cannot see the same path twice !");
+        if !seen.insert(canonical) {
+            continue;
+        }
+        match list_files(root, &subdir, depth + 1, opts, seen, entry_paths) {
+            Err(Error::Io { path, source }) if path == subdir => {
+                eprintln!("WWW Can't scan `{}` : {}", path.display(), source)
             }
+            result => result?,
+        }
+    }
+    Ok(())
+}
 
-            match Package::compare_versions(&pkg, existing_pkg) {
-                // The new one is greater than the already found one.
-                Ordering::Greater => {
-                    if DEBUG_VERSIONS_COMPARISON {
-                        eprintln!(
-                            "=====> Keeping ver. `{}` over `{}`.",
-                            pkg.pkgver, existing_pkg.pkgver
-                        );
-                    }
+/// Whether `path`, found below `root`, matches one of the `exclude` patterns by its name or
+/// its path relative to `root`.
+fn is_excluded(root: &Path, path: &Path, exclude: &[String]) -> bool {
+    let name = path.file_name().map(|n| n.to_string_lossy());
+    let relative = path.strip_prefix(root).unwrap_or(path).to_string_lossy();
+    exclude.iter().any(|pattern| {
+        glob_match(pattern, &relative) || name.as_ref().is_some_and(|n| glob_match(pattern, n))
+    })
+}
 
-                    // Switching places (and ownership) of two packages.
-                    let existing_pkg = new_pkgs.insert(pkg.name, Packages::new(pkg)).unwrap();
-                    let pkg = new_pkgs.get_mut(&existing_pkg.name).unwrap();
-
-                    // We check if we are Greater than all ambiguities as well.
-                    // TODO: ideally add them back to this loop as long as there are any for better
-                    // handling
-                    for p in existing_pkg.into_iter() {
-                        match Package::compare_versions(&p, pkg) {
-                            Ordering::Less if opts.auto_confirm_level.is_everything() => {
-                                pkg.add_ambiguity(p)
-                            }
-                            Ordering::Less => old_pkgs.push(p.path.clone()),
-                            Ordering::Greater => {
-                                eprintln!("WWW Ambiguous package from older version is seen with greater version than the newer one has.");
-                                pkg.add_ambiguity(p);
-                            }
-                            Ordering::Equal => pkg.add_ambiguity(p),
-                        }
-                    }
-                }
-                Ordering::Less if opts.auto_confirm_level.is_everything() => {
-                    existing_pkg.add_ambiguity(pkg)
-                }
-                Ordering::Less => {
-                    if DEBUG_VERSIONS_COMPARISON {
-                        eprintln!(
-                            "=====> Keeping ver `{}` over `{}`.",
-                            existing_pkg.pkgver, pkg.pkgver
-                        );
-                    }
-                    old_pkgs.push(pkg.path.clone());
-                }
-                Ordering::Equal => existing_pkg.add_ambiguity(pkg),
-            }
+/// Whether the file name of `path` looks like a package archive or its signature.
+pub(crate) fn is_package_name(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().contains(".pkg.tar"))
+}
+
+/// Sorts `entry_paths` into packages (with their signature) and ignored files.
+/// Returns : `(pkgs, ignored_files)`
+#[cfg_attr(not(feature = "pkginfo"), allow(unused_variables))]
+fn parse_packages(entry_paths: &[PathBuf], read_pkginfo: bool) -> (Vec<Package>, Vec<IgnoredFile>) {
+    let mut pkgs = Vec::with_capacity(entry_paths.len());
+    // Package path => signature path
+    let mut sig_files = HashMap::new();
+    let mut ignored_files = Vec::new();
+
+    for entry_path in entry_paths.iter() {
+        if entry_path.extension().is_some_and(|s| s == "sig") {
+            sig_files.insert(entry_path.with_extension(""), entry_path.clone());
+            continue;
+        }
+
+        #[cfg(feature = "pkginfo")]
+        let pkg = if read_pkginfo {
+            Package::from_archive(entry_path)
         } else {
-            new_pkgs.insert(pkg.name, Packages::new(pkg));
+            Package::from_path(entry_path)
+        };
+        #[cfg(not(feature = "pkginfo"))]
+        let pkg = Package::from_path(entry_path);
+
+        match pkg {
+            Ok(pkg) => pkgs.push(pkg),
+            #[cfg(feature = "pkginfo")]
+            Err(Error::Parse {
+                path,
+                kind: PackageParseError::MetadataMismatch { file_name, pkginfo },
+            }) => ignored_files.push(IgnoredFile {
+                path,
+                reason: IgnoreReason::MetadataMismatch { file_name, pkginfo },
+            }),
+            Err(Error::Parse { path, kind }) => ignored_files.push(IgnoredFile {
+                path,
+                reason: IgnoreReason::NotAPackage(kind),
+            }),
+            Err(Error::Io { path, source }) => ignored_files.push(IgnoredFile {
+                path,
+                reason: IgnoreReason::Unreadable(source),
+            }),
+            Err(err) => ignored_files.push(IgnoredFile {
+                path: entry_path.clone(),
+                reason: IgnoreReason::Unreadable(io::Error::other(err.to_string())),
+            }),
         }
     }
 
-    let mut single_new_pkgs = Vec::with_capacity(new_pkgs.len());
-    println!("\n------------");
-    if opts.auto_confirm_level.is_everything() {
-        println!(
-            "Given the auto-confirm level set to everything, we're asking for every package...\n"
-        );
-    } else {
-        println!("Handling ambiguous versions...\n");
+    // A signature goes wherever its package goes, and is ignored if there is no package.
+    for pkg in pkgs.iter_mut() {
+        pkg.sig = sig_files.remove(&pkg.path);
     }
-    for p in new_pkgs.into_values() {
-        if !p.has_ambs() {
-            single_new_pkgs.push(p.into_iter().next().unwrap());
-        } else {
-            let name = p.get_name().to_string();
-            let mut ambs: Vec<_> = p.into_iter().collect();
-            // index 0 should always exist
-            println!(
-                "Package `{}` has {} {} :",
-                name,
-                ambs.len(),
-                if opts.auto_confirm_level.is_everything() {
-                    "versions"
-                } else {
-                    "ambiguities"
-                }
-            );
-            // We get the "biggest" string on top.
-            // TODO: si chrono, comparer dates versions
-            ambs.sort_by(|a, b| b.pkgverstr.cmp(a.pkgverstr));
-            ambs.iter().enumerate().rev().for_each(|(i, p)| {
-                #[cfg(feature = "chrono")]
-                {
-                    let date: chrono::DateTime<chrono::Local> =
-                        chrono::DateTime::from(metadata(p.path).unwrap().created().unwrap());
-                    println!("{:2}.\t{}\t(created {})", i, p.pkgver, date.to_rfc2822())
-                }
-                #[cfg(not(feature = "chrono"))]
-                println!("{:2}.\t{}", i, p.pkgver)
-            });
+    ignored_files.extend(sig_files.into_values().map(|path| IgnoredFile {
+        path,
+        reason: IgnoreReason::OrphanSignature,
+    }));
 
-            let number_opt = if !opts.auto_confirm_level.is_at_least_ambiguities() {
-                println!("> keeping all");
-                None
-            } else {
-                loop {
-                    println!("> The index corresponding to the version to keep (default 0), or `i` to ignore :");
-                    let mut input = String::new();
-                    if let Err(err) = io::stdin().read_line(&mut input) {
-                        // TODO: not panic ?
-                        panic!(
-                            "EEE Can't read from input to ask anything to the user: {}",
-                            err
-                        );
-                    }
-                    // We remove the line feed.
-                    input.truncate(input.len() - 1);
-
-                    if input.is_empty() {
-                        break Some(0);
-                    } else if input == "i" {
-                        break None;
-                    } else {
-                        match usize::from_str(&input[..]) {
-                            Err(err) => {
-                                eprintln!(
-                                    "WWW Can't parse input `{}` into number : {}",
-                                    input, err
-                                );
-                            }
-                            Ok(number) => {
-                                if number < ambs.len() {
-                                    break Some(number);
-                                } else {
-                                    eprintln!("WWW parsed number {} from `{}` is too high, please provide a number between 0 and {}.", number, input, ambs.len());
-                                }
-                            }
-                        }
-                    }
-                }
-            };
+    (pkgs, ignored_files)
+}
 
-            if let Some(number) = number_opt {
-                ambs.drain(..).enumerate().for_each(|(i, p)| {
-                    if i == number {
-                        single_new_pkgs.push(p)
-                    } else {
-                        old_pkgs.push(p.path.clone())
-                    }
-                });
-            } else {
-                // TODO remove clone : require carrying a ref to the owner or something
-                // to move it...
-                ignored_files.extend(ambs.drain(..).map(|p| p.path.clone()));
+/// Attaches each package identical to one found before (same name, version, size and sha256)
+/// to it as a duplicate, so that the copies from the following directories go wherever the
+/// first one goes instead of being seen as ambiguous versions.
+///
+/// With [`Grouping::PerDirectory`], only the copies in the same directory are merged, which
+/// can't be as they have the same name.
+fn merge_duplicates(pkgs: Vec<Package>, group_by: Grouping) -> Vec<Package> {
+    let size = |p: &Package| std::fs::metadata(&p.path).map(|m| m.len()).ok();
+    let digest = |p: &Package| journal::digest(&p.path).ok().map(|(_, sha256)| sha256);
+
+    let mut merged: Vec<Package> = Vec::with_capacity(pkgs.len());
+    // (name, version, size, group) => indices in `merged`
+    type Key = (String, String, u64, Option<PathBuf>);
+    let mut candidates: HashMap<Key, Vec<usize>> = HashMap::new();
+    for pkg in pkgs {
+        let Some(pkg_size) = size(&pkg) else {
+            merged.push(pkg);
+            continue;
+        };
+        let key = (
+            pkg.name.clone(),
+            pkg.pkgver.clone(),
+            pkg_size,
+            group_by.group_of(&pkg.path),
+        );
+        let indices = candidates.entry(key).or_default();
+        let pkg_digest = (!indices.is_empty()).then(|| digest(&pkg)).flatten();
+        let original = indices.iter().copied().find(|&i| {
+            pkg_digest.is_some() && merged[i].path != pkg.path && digest(&merged[i]) == pkg_digest
+        });
+        match original {
+            Some(i) => merged[i].duplicates.push(pkg),
+            None => {
+                indices.push(merged.len());
+                merged.push(pkg);
             }
         }
     }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::process;
+
+    fn pkg(name: &str, pkgver: &str) -> Package {
+        Package::from_path(Path::new(&format!(
+            "/cache/{}-{}-x86_64.pkg.tar.zst",
+            name, pkgver
+        )))
+        .unwrap()
+    }
+
+    /// A plan with one ambiguity per name, between `2.0-1` and `2_0-1`, one of them to keep.
+    fn ambiguous_plan(names: &[&str]) -> CleanupPlan {
+        CleanupPlan {
+            ambiguities: names
+                .iter()
+                .map(|name| Ambiguity {
+                    name: name.to_string(),
+                    versions: vec![pkg(name, "2.0-1"), pkg(name, "2_0-1")],
+                    slots: 1,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
 
-    // If a sig file corresponds to an old package, we remove it as well, and if it doesn't
-    // correpsond to a package to keep, we ignore it.
-    for sig_path in sig_files.drain(..) {
-        if old_pkgs.iter().any(|p| p.eq(&sig_path.with_extension(""))) {
-            old_pkgs.push(sig_path.clone());
-        } else if !single_new_pkgs
+    fn versions(pkgs: &[Package]) -> Vec<&str> {
+        pkgs.iter().map(|p| &p.pkgver[..]).collect()
+    }
+
+    #[test]
+    fn resolve_with_a_choice() {
+        let mut plan = ambiguous_plan(&["foo"]);
+        let mut prompter = ScriptedPrompter::new([Some(vec![1])], []);
+        resolve_ambiguities(&mut plan, &mut prompter).unwrap();
+        assert_eq!(versions(&plan.kept), ["2_0-1"]);
+        assert_eq!(versions(&plan.old), ["2.0-1"]);
+        assert!(plan.ambiguities.is_empty());
+    }
+
+    #[test]
+    fn resolve_ignoring() {
+        let mut plan = ambiguous_plan(&["foo"]);
+        let mut prompter = ScriptedPrompter::new([None], []);
+        resolve_ambiguities(&mut plan, &mut prompter).unwrap();
+        assert!(plan.kept.is_empty() && plan.old.is_empty());
+        assert_eq!(plan.ignored.len(), 2);
+        assert!(plan
+            .ignored
             .iter()
-            .map(|p| &p.path)
-            .any(|p| (*p).eq(&sig_path.with_extension("")))
-        {
-            ignored_files.push(sig_path.clone());
+            .all(|f| matches!(f.reason, IgnoreReason::Ambiguous)));
+    }
+
+    #[test]
+    fn resolve_out_of_range() {
+        let mut plan = ambiguous_plan(&["foo"]);
+        let mut prompter = ScriptedPrompter::new([Some(vec![2])], []);
+        resolve_ambiguities(&mut plan, &mut prompter).unwrap();
+        assert!(plan.kept.is_empty() && plan.old.is_empty());
+        assert_eq!(plan.ignored.len(), 2);
+    }
+
+    #[test]
+    fn resolve_exhausted_script() {
+        let mut plan = ambiguous_plan(&["bar", "foo"]);
+        let mut prompter = ScriptedPrompter::new([Some(vec![0])], []);
+        assert!(resolve_ambiguities(&mut plan, &mut prompter).is_err());
+        // The first one is resolved, the other is still there.
+        assert_eq!(versions(&plan.kept), ["2.0-1"]);
+        assert_eq!(plan.ambiguities.len(), 1);
+        assert_eq!(plan.ambiguities[0].name, "foo");
+    }
+
+    /// A cache with `foo` `1.0-1` and the ambiguous `2.0-1` and `2_0-1`, an empty local
+    /// database and a state directory, all in a fresh directory named after `test`.
+    fn fake_cache(test: &str) -> (PathBuf, Options) {
+        let root =
+            std::env::temp_dir().join(format!("remove_old_arch_pkgs-{}-{}", test, process::id()));
+        let _ = remove_dir_all(&root);
+        let cache = root.join("cache");
+        create_dir_all(&cache).unwrap();
+        create_dir_all(root.join("db/local")).unwrap();
+        for pkgver in ["1.0-1", "2.0-1", "2_0-1"] {
+            write(
+                cache.join(format!("foo-{}-x86_64.pkg.tar.zst", pkgver)),
+                pkgver,
+            )
+            .unwrap();
         }
+
+        let opts = Options {
+            dirs: vec![cache],
+            dbpath: root.join("db"),
+            state_dir: root.join("state"),
+            ..Options::default()
+        };
+        (root, opts)
     }
 
-    // Ideally I might not sort them here as it is purely aesthetical, but for such a simple prog,
-    // it's okay.
-    old_pkgs.sort();
-    ignored_files.sort();
+    fn cached(root: &Path) -> Vec<String> {
+        let mut names: Vec<String> = read_dir(root.join("cache"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
 
-    Ok((old_pkgs, ignored_files))
-}
+    #[test]
+    fn clean_with_a_choice() {
+        let (root, opts) = fake_cache("clean-choice");
+        let mut prompter = ScriptedPrompter::new([Some(vec![0])], [true]);
+        let plan = clean(&opts, &mut prompter).unwrap();
+        let left = cached(&root);
+        remove_dir_all(&root).unwrap();
 
-fn list_removed_files(files: &[PathBuf]) {
-    println!("\n------------");
-    println!("{} files about to be removed...\n", files.len());
-    files
-        .iter()
-        .map(|path| path.to_str().unwrap())
-        .for_each(|path| println!("{}", path));
-}
+        assert_eq!(plan.kept.len(), 1);
+        assert_eq!(plan.old.len(), 2);
+        assert_eq!(
+            left,
+            [format!("foo-{}-x86_64.pkg.tar.zst", plan.kept[0].pkgver)]
+        );
+    }
 
-fn list_ignored_files(ignored_files: &[PathBuf]) {
-    println!("\n------------");
-    println!("{} files ignored...\n", ignored_files.len());
-    ignored_files
-        .iter()
-        .map(|path| path.to_str().unwrap())
-        .for_each(|path| println!("{}", path));
-}
+    #[test]
+    fn clean_not_confirmed() {
+        let (root, opts) = fake_cache("clean-not-confirmed");
+        let mut prompter = ScriptedPrompter::new([None], [false]);
+        clean(&opts, &mut prompter).unwrap();
+        let left = cached(&root);
+        remove_dir_all(&root).unwrap();
+        assert_eq!(left.len(), 3);
+    }
 
-fn remove_files<P: AsRef<Path>>(files: Vec<P>) -> io::Result<()> {
-    println!("\n------------");
-    println!("Actually removing {} files...\n", files.len());
-    for file in files.iter() {
-        println!("{}", file.as_ref().file_name().unwrap().to_str().unwrap());
-        remove_file(file)?;
+    #[test]
+    fn clean_exhausted_script() {
+        let (root, opts) = fake_cache("clean-exhausted");
+        let mut prompter = ScriptedPrompter::new([Some(vec![0])], []);
+        let result = clean(&opts, &mut prompter);
+        let left = cached(&root);
+        remove_dir_all(&root).unwrap();
+        assert!(matches!(result, Err(Error::Aborted(_))));
+        assert_eq!(left.len(), 3);
     }
-    Ok(())
 }
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::Path;
+
+/// Default pacman database directory, as `DBPath` in `pacman.conf`.
+pub const DEFAULT_DBPATH: &str = "/var/lib/pacman/";
+
+/// A package installed on the system.
+#[derive(Debug, Clone)]
+pub struct InstalledPackage {
+    pub name: String,
+    /// Full version : `[epoch:]pkgver-pkgrel`
+    pub version: String,
+    pub groups: Vec<String>,
+}
+
+/// Installed packages, read from pacman's local database (`{dbpath}/local/*/desc`).
+#[derive(Debug, Clone, Default)]
+pub struct LocalDb {
+    pkgs: HashMap<String, InstalledPackage>,
+}
+
+impl LocalDb {
+    /// Reads the local database under `dbpath` (`/var/lib/pacman/` by default).
+    ///
+    /// Only failing to list the database is an error : a package entry that can't be read is
+    /// skipped with a warning.
+    pub fn read(dbpath: &Path) -> io::Result<Self> {
+        let mut pkgs = HashMap::new();
+
+        for entry in read_dir(dbpath.join("local"))? {
+            let desc_path = entry?.path().join("desc");
+            // There is also an `ALPM_DB_VERSION` file in there.
+            if !desc_path.is_file() {
+                continue;
+            }
+
+            let desc = match read_to_string(&desc_path) {
+                Ok(desc) => desc,
+                Err(err) => {
+                    eprintln!(
+                        "WWW Can't read `{}`, ignoring it : {}",
+                        desc_path.display(),
+                        err
+                    );
+                    continue;
+                }
+            };
+            match parse_desc(&desc) {
+                Some(pkg) => {
+                    pkgs.insert(pkg.name.clone(), pkg);
+                }
+                None => eprintln!(
+                    "WWW `{}` doesn't have a name or a version, ignoring it.",
+                    desc_path.display()
+                ),
+            }
+        }
+
+        Ok(LocalDb { pkgs })
+    }
+
+    pub fn len(&self) -> usize {
+        self.pkgs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pkgs.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&InstalledPackage> {
+        self.pkgs.get(name)
+    }
+
+    /// Is this exact version of the package installed ?
+    pub fn is_installed(&self, name: &str, version: &str) -> bool {
+        self.get(name).is_some_and(|p| p.version == version)
+    }
+}
+
+/// Extracts the `%NAME%`, `%VERSION%` and `%GROUPS%` fields of a `desc` file.
+fn parse_desc(desc: &str) -> Option<InstalledPackage> {
+    let mut name = None;
+    let mut version = None;
+    let mut groups = Vec::new();
+
+    let mut lines = desc.lines();
+    while let Some(line) = lines.next() {
+        match line {
+            "%NAME%" => name = lines.next(),
+            "%VERSION%" => version = lines.next(),
+            // One group per line, up to an empty line.
+            "%GROUPS%" => groups.extend(
+                lines
+                    .by_ref()
+                    .take_while(|l| !l.is_empty())
+                    .map(str::to_string),
+            ),
+            _ => (),
+        }
+    }
+
+    Some(InstalledPackage {
+        name: name?.to_string(),
+        version: version?.to_string(),
+        groups,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::process;
+
+    #[test]
+    fn parse_desc_fields() {
+        let desc = "%NAME%\nlinux\n\n%VERSION%\n1:6.1.2.arch1-1\n\n%GROUPS%\nbase\nkernels\n\n%ARCH%\nx86_64\n";
+        let pkg = parse_desc(desc).unwrap();
+        assert_eq!(pkg.name, "linux");
+        assert_eq!(pkg.version, "1:6.1.2.arch1-1");
+        assert_eq!(pkg.groups, ["base", "kernels"]);
+    }
+
+    #[test]
+    fn parse_desc_without_version() {
+        assert!(parse_desc("%NAME%\nlinux\n").is_none());
+        assert!(parse_desc("%VERSION%\n1.0-1\n").is_none());
+    }
+
+    #[test]
+    fn read_fake_db() {
+        let dbpath =
+            std::env::temp_dir().join(format!("remove_old_arch_pkgs-localdb-{}", process::id()));
+        let local = dbpath.join("local");
+        create_dir_all(local.join("foo-1.0-1")).unwrap();
+        create_dir_all(local.join("bar-2.0-1")).unwrap();
+        create_dir_all(local.join("noversion-1-1")).unwrap();
+        write(local.join("ALPM_DB_VERSION"), "9\n").unwrap();
+        write(
+            local.join("foo-1.0-1/desc"),
+            "%NAME%\nfoo\n\n%VERSION%\n1.0-1\n",
+        )
+        .unwrap();
+        // Not UTF-8 : skipped, the others are still read.
+        write(local.join("bar-2.0-1/desc"), b"%NAME%\nbar\xff\n").unwrap();
+        write(local.join("noversion-1-1/desc"), "%NAME%\nnoversion\n").unwrap();
+
+        let db = LocalDb::read(&dbpath).unwrap();
+        remove_dir_all(&dbpath).unwrap();
+        assert_eq!(db.len(), 1);
+        assert!(db.is_installed("foo", "1.0-1"));
+        assert!(!db.is_installed("foo", "1.1-1"));
+        assert!(db.get("bar").is_none());
+    }
+
+    #[test]
+    fn read_missing_db() {
+        assert!(LocalDb::read(Path::new("/nonexistent/remove_old_arch_pkgs")).is_err());
+    }
+}
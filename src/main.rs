@@ -1,62 +1,722 @@
 extern crate remove_old_arch_pkgs;
 
-use std::env::args;
-use std::path::PathBuf;
+mod cli;
+
+use std::collections::HashSet;
+use std::fs::metadata;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use clap::{CommandFactory, Parser};
+
+use remove_old_arch_pkgs::{
+    apply, apply_saved_plan, compare_backends, ordering_symbol, plan, plan_purge, purge,
+    resolve_ambiguities, restore, save_plan, save_script, AlpmComparator, Ambiguity, CleanupPlan,
+    Disagreement, Error, IgnoreReason, IgnoredFile, Journal, Options, OutputFormat, Package,
+    PlanFile, Prompter, Result, TerminalPrompter, VersionComparator, VersionCompareComparator,
+    DEFAULT_CACHEDIR,
+};
 
-use remove_old_arch_pkgs::{remove_old_archlinux_packages, AutoConfirmLevel, Options};
+use cli::{CleanArgs, Cli, Command, ScanArgs, DAY};
 
 const EXIT_UNKNOWN_OPT: i32 = 1;
 const EXIT_NOT_A_DIR: i32 = 2;
 const EXIT_IO_ERROR: i32 = 3;
+const EXIT_NO_ANSWER: i32 = 4;
+const EXIT_LOCKED: i32 = 6;
+const EXIT_PARTIAL_REMOVAL: i32 = 7;
+const EXIT_STALE_PLAN: i32 = 8;
+const EXIT_PROBLEMS_FOUND: i32 = 9;
+
+/// Set with `--format json` : stdout is kept for the JSON document.
+static STDOUT_IS_JSON: AtomicBool = AtomicBool::new(false);
+
+/// `println!`, or `eprintln!` when stdout is kept for the JSON document.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if STDOUT_IS_JSON.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 fn main() {
-    let mut opts = Options::default();
-    let mut dir_given = false;
-
-    for arg in args().skip(1) {
-        match &arg[..] {
-            "-h" => {
-                println!(include_str!("../README.md"));
-                return;
-            }
-            "-d" => opts.dry_run = true,
-            "-0" => opts.auto_confirm_level = AutoConfirmLevel::Nothing,
-            "-1" => opts.auto_confirm_level = AutoConfirmLevel::Removal,
-            "-2" => opts.auto_confirm_level = AutoConfirmLevel::Ambiguities,
-            "-3" => opts.auto_confirm_level = AutoConfirmLevel::Everything,
-            dir if dir.starts_with('-') => {
-                eprintln!("Error: unknown option `{}`.", dir);
-                exit(EXIT_UNKNOWN_OPT);
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // `--help` and `--version` are "errors" printed on stdout.
+        Err(err) => {
+            let _ = err.print();
+            exit(if err.use_stderr() {
+                EXIT_UNKNOWN_OPT
+            } else {
+                0
+            });
+        }
+    };
+
+    let result = match cli.command.unwrap_or(Command::Clean(cli.clean)) {
+        Command::Clean(args) => clean_cache(args),
+        Command::List(args) => {
+            let mut opts = scan_options(&args.scan);
+            args.report.configure(&mut opts);
+            STDOUT_IS_JSON.store(opts.format == OutputFormat::Json, Ordering::Relaxed);
+            list_plan(&opts, None)
+        }
+        Command::Plan(args) => {
+            let mut opts = scan_options(&args.scan);
+            args.report.configure(&mut opts);
+            STDOUT_IS_JSON.store(opts.format == OutputFormat::Json, Ordering::Relaxed);
+            list_plan(&opts, Some(&args.output))
+        }
+        Command::Stats(args) => print_stats(&scan_options(&args)),
+        Command::Verify(args) => verify_cache(&scan_options(&args)).map(|problems| {
+            if problems > 0 {
+                exit(EXIT_PROBLEMS_FOUND);
             }
-            dir => {
-                opts.dir = PathBuf::from(&dir);
-
-                if !opts.dir.is_dir() {
-                    eprintln!(
-                        "Error: provided argument `{}` is not a directory or can't be found.",
-                        dir
-                    );
-                    exit(EXIT_NOT_A_DIR);
-                }
-
-                dir_given = true;
+        }),
+        Command::CompareBackends(args) => {
+            compare_backends(&scan_options(&args)).map(|d| list_disagreements(&d))
+        }
+        Command::Apply(args) => {
+            let dirs = if !args.dirs.is_empty() {
+                args.dirs
+            } else if let Some(config) = &args.config {
+                Options::from_pacman_conf(config)
+                    .unwrap_or_else(|err| exit_with(err))
+                    .dirs
+            } else {
+                vec![PathBuf::from(DEFAULT_CACHEDIR)]
+            };
+            let mut opts = Options {
+                dirs,
+                dbpath: args.dbpath,
+                ..Options::default()
+            };
+            args.prompt.configure(&mut opts);
+            args.removal.configure(&mut opts);
+            apply_plan_file(&opts, &args.plan, args.skip_changed)
+        }
+        Command::Restore(args) => {
+            let opts = Options {
+                dbpath: args.dbpath,
+                dry_run: args.dry_run,
+                ..Options::default()
+            };
+            restore_journal(&opts, &args.journal)
+        }
+        Command::Purge(args) => {
+            let mut opts = Options {
+                dbpath: args.dbpath,
+                ..Options::default()
+            };
+            if let Some(state_dir) = args.state_dir {
+                opts.state_dir = state_dir;
             }
+            args.prompt.configure(&mut opts);
+            purge_quarantine(&opts, &args.quarantine, args.older_than)
         }
+        Command::Completions { shell } => {
+            let mut cmd = Cli::command();
+            let name = cmd.get_name().to_string();
+            clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+            Ok(())
+        }
+    };
+
+    if let Err(err) = result {
+        exit_with(err);
     }
+}
+
+/// The options to scan the caches given in `args`, exits if one of them isn't a directory.
+fn scan_options(args: &ScanArgs) -> Options {
+    let mut opts = match &args.config {
+        Some(config) => Options::from_pacman_conf(config).unwrap_or_else(|err| exit_with(err)),
+        None => Options::default(),
+    };
+    args.configure(&mut opts);
+
+    if let Some(dir) = opts.dirs.iter().find(|dir| !dir.is_dir()) {
+        eprintln!(
+            "Error: provided argument `{}` is not a directory or can't be found.",
+            dir.display()
+        );
+        exit(EXIT_NOT_A_DIR);
+    }
+    opts
+}
+
+fn clean_cache(args: CleanArgs) -> Result<()> {
+    let mut opts = scan_options(&args.scan);
+    args.prompt.configure(&mut opts);
+    args.removal.configure(&mut opts);
+    args.report.configure(&mut opts);
+    STDOUT_IS_JSON.store(opts.format == OutputFormat::Json, Ordering::Relaxed);
 
-    if !dir_given {
+    if args.scan.dirs.is_empty() && args.scan.config.is_none() {
         eprintln!("No folder was provided, using current working directory...");
     } else {
-        eprintln!("Cleaning directory : {}", opts.dir.to_string_lossy());
+        for dir in opts.dirs.iter() {
+            eprintln!("Cleaning directory : {}", dir.to_string_lossy());
+        }
     }
     eprintln!("Selected confirm level : {}...", opts.auto_confirm_level);
+    eprintln!("Comparing versions with : {}...", opts.comparator);
+    eprintln!(
+        "Keeping the {} most recent versions of each package...",
+        opts.keep_versions
+    );
+    if let Some(keep_uninstalled) = opts.keep_uninstalled {
+        eprintln!(
+            "Keeping the {} most recent versions of uninstalled packages...",
+            keep_uninstalled
+        );
+    }
+    if let Some(quarantine) = &opts.move_to {
+        eprintln!(
+            "Moving old packages to the quarantine directory : {}...",
+            quarantine.display()
+        );
+    }
+    if opts.trash {
+        eprintln!("Moving old packages to the trash...");
+    }
     if opts.dry_run {
         eprintln!("Dry run enabled, nothing will be deleted.");
     }
 
-    if let Err(err) = remove_old_archlinux_packages(opts) {
-        eprintln!("An io error occurred : `{}`", err);
-        exit(EXIT_IO_ERROR);
+    remove_old_archlinux_packages(opts, args.output, args.emit_script)
+}
+
+/// Reports `err` and exits with the matching code, see the README.
+fn exit_with(err: Error) -> ! {
+    let code = match &err {
+        Error::Io { .. } => {
+            eprintln!("An io error occurred : {}", err);
+            EXIT_IO_ERROR
+        }
+        // Not returned by the commands, which list the files that aren't packages as ignored.
+        Error::Parse { .. } => {
+            eprintln!("Error: {}.", err);
+            EXIT_IO_ERROR
+        }
+        Error::Aborted(no_answer) => {
+            say!("\n------------");
+            say!("Abording : {}, not removing any file.", no_answer);
+            EXIT_NO_ANSWER
+        }
+        Error::Locked(_) => {
+            eprintln!("Error: {}.", err);
+            EXIT_LOCKED
+        }
+        Error::Stale(changed) => {
+            eprintln!("Error: {} :", err);
+            changed
+                .iter()
+                .for_each(|(path, change)| eprintln!("{}\t({})", path.display(), change));
+            EXIT_STALE_PLAN
+        }
+        // The files have been listed by `remove_files` already.
+        Error::PartialRemoval { .. } => {
+            eprintln!("Error: {}.", err);
+            EXIT_PARTIAL_REMOVAL
+        }
+    };
+    exit(code);
+}
+
+fn remove_old_archlinux_packages(
+    opts: Options,
+    plan_output: Option<PathBuf>,
+    script_output: Option<PathBuf>,
+) -> Result<()> {
+    let mut prompter = TerminalPrompter::new(&opts);
+    let mut plan = plan(&opts)?;
+
+    if opts.explain {
+        say!("\n------------");
+        say!("Explaining the decisions...\n");
+        plan.explanations.iter().for_each(|e| say!("{}", e));
+    }
+
+    say!("\n------------");
+    if opts.auto_confirm_level.is_everything() {
+        say!("Given the auto-confirm level set to everything, we're asking for every package...\n");
+    } else {
+        say!("Handling ambiguous versions...\n");
+    }
+    resolve_ambiguities(&mut plan, &mut prompter)?;
+
+    let old_files = plan.files_to_remove();
+    if opts.format == OutputFormat::Json {
+        println!("{}", plan.to_json());
+    } else {
+        list_duplicates(&plan);
+        list_removed_files(&plan);
+        list_ignored_files(&plan.ignored);
+    }
+    if plan_output.is_some() || script_output.is_some() {
+        say!("\n------------");
+        if let Some(output) = plan_output {
+            save_plan(&plan, &opts, &output)?;
+            say!(
+                "Plan saved to {}, nothing removed : apply it with `apply {}`.",
+                output.display(),
+                output.display()
+            );
+        }
+        if let Some(output) = script_output {
+            save_script(&plan, &opts, &output)?;
+            say!(
+                "Script saved to {}, nothing removed : review it, then run it with `sh {}`.",
+                output.display(),
+                output.display()
+            );
+        }
+    } else if !opts.dry_run {
+        if prompter.confirm_removal(&old_files)? {
+            remove_files(&plan, &opts)?;
+
+            // It has'n been shown before
+            if !opts.auto_confirm_level.is_at_least_removal() && opts.format == OutputFormat::Text {
+                list_ignored_files(&plan.ignored);
+            }
+        } else {
+            say!("\n------------");
+            say!("Abording : Not removing any file.");
+        }
+    }
+
+    Ok(())
+}
+
+/// Removes the files of the plan saved at `saved`, if they haven't changed since.
+fn apply_plan_file(opts: &Options, saved: &Path, skip_changed: bool) -> Result<()> {
+    let mut prompter = TerminalPrompter::new(opts);
+    let plan = PlanFile::read(saved).map_err(|err| Error::io(saved, err))?;
+    plan.check_files(&opts.dirs)
+        .map_err(|err| Error::io(saved, err))?;
+    let changed = plan.changed_files();
+
+    say!("\n------------");
+    say!(
+        "{} files planned for removal in {}...\n",
+        plan.files.len(),
+        saved.display()
+    );
+    plan.files
+        .iter()
+        .for_each(|f| match changed.iter().find(|(path, _)| *path == f.path) {
+            Some((_, change)) => say!("{}\t({})", f.path.display(), change),
+            None => say!("{}", f.path.display()),
+        });
+
+    if !changed.is_empty() && !skip_changed {
+        return Err(Error::Stale(changed));
+    } else if opts.dry_run {
+        return Ok(());
+    }
+    let files: Vec<&Path> = plan.files.iter().map(|f| f.path.as_path()).collect();
+    if prompter.confirm_removal(&files)? {
+        let verb = if opts.move_to.is_some() || opts.trash {
+            "moved"
+        } else {
+            "removed"
+        };
+        let count = plan.files.len() - changed.len();
+        report_removal(
+            apply_saved_plan(&plan, saved, &changed, opts, skip_changed),
+            count,
+            verb,
+        )
+    } else {
+        say!("\n------------");
+        say!("Abording : Not removing any file.");
+        Ok(())
+    }
+}
+
+/// Puts back the files quarantined by the run recorded in `journal`.
+fn restore_journal(opts: &Options, journal: &Path) -> Result<()> {
+    let entries = Journal::read(journal)
+        .map_err(|err| Error::io(journal, err))?
+        .entries;
+
+    say!("\n------------");
+    say!(
+        "{} files recorded in {}...\n",
+        entries.len(),
+        journal.display()
+    );
+    entries.iter().for_each(|e| match &e.moved_to {
+        Some(moved_to) => say!("{}\t(from {})", e.path.display(), moved_to.display()),
+        None => say!("{}\t(removed for good)", e.path.display()),
+    });
+
+    if opts.dry_run {
+        return Ok(());
+    }
+    let result = restore(journal, opts);
+    let count = result.as_ref().map_or(0, Vec::len);
+    report_removal(result.map(|_| None), count, "restored")
+}
+
+/// Removes the files that have been in `quarantine` for more than `days` days.
+fn purge_quarantine(opts: &Options, quarantine: &Path, days: u64) -> Result<()> {
+    let mut prompter = TerminalPrompter::new(opts);
+    // `parse_days` checked it doesn't overflow.
+    let files = plan_purge(quarantine, Duration::from_secs(days * DAY))?;
+
+    say!("\n------------");
+    say!(
+        "{} files quarantined for more than {} days...\n",
+        files.len(),
+        days
+    );
+    files.iter().for_each(|path| say!("{}", path.display()));
+
+    if opts.dry_run {
+        return Ok(());
+    }
+    let paths: Vec<&Path> = files.iter().map(PathBuf::as_path).collect();
+    if prompter.confirm_removal(&paths)? {
+        report_removal(purge(&files, opts), files.len(), "removed")
+    } else {
+        say!("\n------------");
+        say!("Abording : Not removing any file.");
+        Ok(())
+    }
+}
+
+/// Prints what a cleaning would do, without asking anything, and saves it to `output` if given.
+fn list_plan(opts: &Options, output: Option<&Path>) -> Result<()> {
+    let plan = plan(opts)?;
+
+    if opts.explain {
+        say!("\n------------");
+        say!("Explaining the decisions...\n");
+        plan.explanations.iter().for_each(|e| say!("{}", e));
+    }
+
+    if opts.format == OutputFormat::Json {
+        println!("{}", plan.to_json());
+    } else {
+        list_kept_packages(&plan);
+        list_duplicates(&plan);
+        list_ambiguities(&plan.ambiguities);
+        list_removed_files(&plan);
+        list_ignored_files(&plan.ignored);
+    }
+    if let Some(output) = output {
+        save_plan(&plan, opts, output)?;
+        say!("\n------------");
+        say!(
+            "Plan saved to {}, nothing removed : apply it with `apply {}`.",
+            output.display(),
+            output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Prints how many packages and versions the cache holds and how much space a cleaning would
+/// free, in each directory if there are several.
+fn print_stats(opts: &Options) -> Result<()> {
+    let plan = plan(opts)?;
+    let ambiguous: Vec<&Package> = plan
+        .ambiguities
+        .iter()
+        .flat_map(|amb| amb.versions.iter())
+        .collect();
+    let packages: Vec<&Package> = plan
+        .kept
+        .iter()
+        .chain(plan.old.iter())
+        .chain(ambiguous.iter().copied())
+        .collect();
+    let names: HashSet<&str> = packages.iter().map(|p| &p.name[..]).collect();
+    let size = |files: &mut dyn Iterator<Item = &Path>| -> u64 {
+        files.map(|f| metadata(f).map_or(0, |m| m.len())).sum()
+    };
+    let all_files: Vec<&Path> = packages
+        .iter()
+        .flat_map(|p| p.files())
+        .chain(plan.ignored.iter().map(|f| f.path.as_path()))
+        .collect();
+    let removed = plan.files_to_remove();
+
+    say!("\n------------");
+    say!("Statistics of {}...\n", join_paths(&unique_dirs(&plan)));
+    say!(
+        "{} packages, {} versions ({} signed, {} with identical copies)",
+        names.len(),
+        packages.len(),
+        packages.iter().filter(|p| p.sig.is_some()).count(),
+        packages.iter().filter(|p| !p.duplicates.is_empty()).count()
+    );
+    say!(
+        "{} versions kept, {} to remove, {} ambiguous, {} files ignored",
+        plan.kept.len(),
+        plan.old.len(),
+        ambiguous.len(),
+        plan.ignored.len()
+    );
+    say!(
+        "Cache size : {}",
+        human_size(size(&mut all_files.iter().copied()))
+    );
+    say!(
+        "Reclaimable : {} ({} files)",
+        human_size(size(&mut removed.iter().copied())),
+        removed.len()
+    );
+
+    if plan.dirs.len() > 1 {
+        for dir in unique_dirs(&plan) {
+            let in_dir = |f: &&Path| plan.source_dir(f) == Some(dir);
+            let removed_in_dir: Vec<&Path> = removed.iter().copied().filter(in_dir).collect();
+            say!(
+                "\n{} : {} files, {}, {} reclaimable ({} files)",
+                dir.display(),
+                all_files.iter().copied().filter(in_dir).count(),
+                human_size(size(&mut all_files.iter().copied().filter(in_dir))),
+                human_size(size(&mut removed_in_dir.iter().copied())),
+                removed_in_dir.len()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Lists the files that aren't packages or don't match their `.PKGINFO`, can't be read or are
+/// signatures without package, then the packages without signature, and returns how many
+/// problems were found.
+///
+/// A missing signature isn't a problem, locally built packages usually have none.
+fn verify_cache(opts: &Options) -> Result<usize> {
+    let plan = plan(opts)?;
+    let problems: Vec<&IgnoredFile> = plan
+        .ignored
+        .iter()
+        .filter(|f| match f.reason {
+            IgnoreReason::NotAPackage(_)
+            | IgnoreReason::Unreadable(_)
+            | IgnoreReason::OrphanSignature => true,
+            #[cfg(feature = "pkginfo")]
+            IgnoreReason::MetadataMismatch { .. } => true,
+            _ => false,
+        })
+        .collect();
+    let mut unsigned: Vec<&Path> = plan
+        .kept
+        .iter()
+        .chain(plan.old.iter())
+        .chain(plan.ambiguities.iter().flat_map(|amb| amb.versions.iter()))
+        .filter(|p| p.sig.is_none())
+        .map(|p| p.path.as_path())
+        .collect();
+    unsigned.sort();
+
+    say!("\n------------");
+    say!("{} packages without signature...\n", unsigned.len());
+    unsigned.iter().for_each(|path| say!("{}", path.display()));
+
+    say!("\n------------");
+    say!("{} problems found...\n", problems.len());
+    problems
+        .iter()
+        .for_each(|f| say!("{}\t({})", f.path.display(), f.reason));
+    Ok(problems.len())
+}
+
+fn list_disagreements(disagreements: &[Disagreement]) {
+    let (left, right) = (AlpmComparator.name(), VersionCompareComparator.name());
+
+    say!("\n------------");
+    say!("Comparing `{}` and `{}`...\n", left, right);
+    disagreements.iter().for_each(|d| {
+        say!(
+            "{} : `{}` vs `{}` : {} `{}`, {} `{}`",
+            d.name,
+            d.version_a,
+            d.version_b,
+            left,
+            ordering_symbol(d.left),
+            right,
+            ordering_symbol(d.right)
+        )
+    });
+    say!("\n------------");
+    say!("{} disagreements found.", disagreements.len());
+}
+
+fn list_removed_files(plan: &CleanupPlan) {
+    let files = plan.files_to_remove();
+    say!("\n------------");
+    say!("{} files about to be removed...\n", files.len());
+    list_by_dir(plan, &files);
+}
+
+fn list_ignored_files(ignored_files: &[IgnoredFile]) {
+    say!("\n------------");
+    say!("{} files ignored...\n", ignored_files.len());
+    ignored_files
+        .iter()
+        .for_each(|f| say!("{}\t({})", f.path.display(), f.reason));
+}
+
+fn remove_files(plan: &CleanupPlan, opts: &Options) -> Result<()> {
+    let files = plan.files_to_remove();
+    say!("\n------------");
+    match &opts.move_to {
+        Some(quarantine) => say!(
+            "Actually moving {} files to {}...\n",
+            files.len(),
+            quarantine.display()
+        ),
+        None if opts.trash => say!("Actually moving {} files to the trash...\n", files.len()),
+        None => say!("Actually removing {} files...\n", files.len()),
+    }
+    for file in files.iter() {
+        say!("{}", file.file_name().unwrap_or_default().to_string_lossy());
+    }
+
+    let verb = if opts.move_to.is_some() || opts.trash {
+        "moved"
+    } else {
+        "removed"
+    };
+    report_removal(apply(plan, opts), files.len(), verb)
+}
+
+/// Prints how many of the `count` files have been `verb` and where the journal is, or the
+/// failures, if any.
+fn report_removal(result: Result<Option<PathBuf>>, count: usize, verb: &str) -> Result<()> {
+    match &result {
+        Ok(journal) => {
+            say!("\n------------");
+            say!("{} files {}.", count, verb);
+            if let Some(journal) = journal {
+                say!("Journal written to {}", journal.display());
+            }
+        }
+        Err(Error::PartialRemoval { removed, failed }) => {
+            say!("\n------------");
+            say!(
+                "{} files {}, {} files couldn't be {}...\n",
+                removed.len(),
+                verb,
+                failed.len(),
+                verb
+            );
+            failed
+                .iter()
+                .for_each(|(path, err)| say!("{}\t({})", path.display(), err));
+        }
+        Err(_) => (),
+    }
+    result.map(drop)
+}
+
+fn list_kept_packages(plan: &CleanupPlan) {
+    let files: Vec<&Path> = plan.kept.iter().map(|p| p.path.as_path()).collect();
+    say!("\n------------");
+    say!("{} packages kept...\n", files.len());
+    list_by_dir(plan, &files);
+}
+
+/// Lists the packages found in several identical copies (in several directories, or under
+/// several names with `--pkginfo`), which go wherever the first copy goes. Prints nothing if
+/// there is none.
+fn list_duplicates(plan: &CleanupPlan) {
+    let packages: Vec<&Package> = plan
+        .kept
+        .iter()
+        .chain(plan.old.iter())
+        .chain(plan.ambiguities.iter().flat_map(|amb| amb.versions.iter()))
+        .filter(|p| !p.duplicates.is_empty())
+        .collect();
+    if packages.is_empty() {
+        return;
+    }
+    say!("\n------------");
+    say!(
+        "{} packages found in several identical copies...\n",
+        packages.len()
+    );
+    packages.iter().for_each(|p| {
+        say!("{}", p.path.display());
+        p.duplicates
+            .iter()
+            .for_each(|d| say!("\tidentical copy {}", d.path.display()));
+    });
+}
+
+/// Prints `files`, under the directory they come from if there are several.
+fn list_by_dir(plan: &CleanupPlan, files: &[&Path]) {
+    if plan.dirs.len() <= 1 {
+        files.iter().for_each(|path| say!("{}", path.display()));
+        return;
+    }
+    for dir in unique_dirs(plan) {
+        let in_dir: Vec<&&Path> = files
+            .iter()
+            .filter(|f| plan.source_dir(f) == Some(dir))
+            .collect();
+        if !in_dir.is_empty() {
+            say!("From {} :", dir.display());
+            in_dir.iter().for_each(|path| say!("\t{}", path.display()));
+        }
+    }
+}
+
+/// The scanned directories of `plan`, each once.
+fn unique_dirs(plan: &CleanupPlan) -> Vec<&Path> {
+    let mut dirs: Vec<&Path> = Vec::new();
+    for dir in plan.dirs.iter() {
+        if !dirs.contains(&dir.as_path()) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+fn join_paths(paths: &[&Path]) -> String {
+    paths
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn list_ambiguities(ambiguities: &[Ambiguity]) {
+    say!("\n------------");
+    say!("{} ambiguities left alone...\n", ambiguities.len());
+    ambiguities.iter().for_each(|amb| {
+        say!("{} ({} to keep) :", amb.name, amb.slots);
+        amb.versions
+            .iter()
+            .for_each(|p| say!("\t{}", p.path.display()));
+    });
+}
+
+/// `bytes` with a binary unit.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
     }
 }
@@ -1,25 +1,130 @@
-use std::{env::current_dir, fmt, path::PathBuf};
+use std::{
+    env::current_dir,
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::compare::ComparatorKind;
+use crate::error;
+use crate::journal::default_state_dir;
+use crate::localdb::DEFAULT_DBPATH;
+use crate::pacman_conf::{PacmanConf, DEFAULT_CACHEDIR};
 
 /// Options for the program
 #[derive(Debug, Clone)]
 pub struct Options {
-    /// Path in which to remove packages
-    pub dir: PathBuf,
+    /// Directories in which to remove packages, seen as one cache : only the newest versions
+    /// among all of them are kept
+    pub dirs: Vec<PathBuf>,
+    /// How many levels of subdirectories are scanned below each directory, `Some(0)` for none
+    /// (the default), `None` for no limit
+    pub max_depth: Option<usize>,
+    /// Whether symbolic links to directories are followed when scanning subdirectories
+    pub follow_symlinks: FollowSymlinks,
+    /// Patterns of the files and subdirectories not to scan, matched against their name and
+    /// their path relative to the directory they were found in
+    pub exclude: Vec<String>,
+    /// Whether the versions of the packages in different subdirectories are compared together
+    pub group_by: Grouping,
     /// Autoconfirm level : how often is the user asked for confirmation
     pub auto_confirm_level: AutoConfirmLevel,
     pub dry_run: bool,
+    /// Fail instead of waiting for an answer when stdin isn't a terminal
+    pub non_interactive: bool,
+    /// How many of the most recent versions of each package to keep
+    pub keep_versions: usize,
+    /// If set, how many versions to keep for packages that aren't installed anymore instead of
+    /// `keep_versions`
+    pub keep_uninstalled: Option<usize>,
+    /// Remove the identical copies of the kept packages found in several directories, keeping
+    /// the one in the first directory
+    pub remove_duplicates: bool,
+    /// Which backend is used to compare versions
+    pub comparator: ComparatorKind,
+    /// Read the name and version of the packages from the `.PKGINFO` in their archive instead
+    /// of their file name (requires the `pkginfo` feature)
+    pub read_pkginfo: bool,
+    /// Which date is shown next to each version when listing ambiguities
+    pub displayed_time: DisplayedTime,
+    /// Pacman database directory, the installed version of each package is always kept
+    pub dbpath: PathBuf,
+    /// Go on without protecting the installed versions if the local database in `dbpath` can't
+    /// be read, instead of failing (unless `keep_uninstalled` is set)
+    pub allow_missing_db: bool,
+    /// Patterns of the packages pacman doesn't upgrade (`IgnorePkg`), none of their versions
+    /// is removed
+    pub ignore_pkgs: Vec<String>,
+    /// Patterns of the groups pacman doesn't upgrade (`IgnoreGroup`), none of the versions of
+    /// their installed packages is removed
+    pub ignore_groups: Vec<String>,
+    /// If set, old packages are moved to this quarantine directory instead of being removed
+    pub move_to: Option<PathBuf>,
+    /// Move old packages to the freedesktop.org trash instead of removing them (ignored if
+    /// `move_to` is set)
+    pub trash: bool,
+    /// Where the journals of the destructive runs are written
+    pub state_dir: PathBuf,
+    /// How the decisions are printed
+    pub format: OutputFormat,
+    /// Explain why each package is kept, removed or ambiguous, see
+    /// [`CleanupPlan::explanations`](crate::CleanupPlan::explanations)
+    pub explain: bool,
 }
 
 impl Default for Options {
     fn default() -> Self {
         Options {
-            dir: current_dir().unwrap(),
+            // The current directory may have been removed, `.` will fail later on.
+            dirs: vec![current_dir().unwrap_or_else(|_| PathBuf::from("."))],
+            max_depth: Some(0),
+            follow_symlinks: Default::default(),
+            exclude: Vec::new(),
+            group_by: Default::default(),
             auto_confirm_level: Default::default(),
             dry_run: false,
+            non_interactive: false,
+            keep_versions: 1,
+            keep_uninstalled: None,
+            remove_duplicates: false,
+            comparator: Default::default(),
+            read_pkginfo: false,
+            displayed_time: Default::default(),
+            dbpath: PathBuf::from(DEFAULT_DBPATH),
+            allow_missing_db: false,
+            ignore_pkgs: Vec::new(),
+            ignore_groups: Vec::new(),
+            move_to: None,
+            trash: false,
+            state_dir: default_state_dir(),
+            format: Default::default(),
+            explain: false,
         }
     }
 }
 
+impl Options {
+    /// The default options, with the `CacheDir` entries, `DBPath`, `IgnorePkg` and
+    /// `IgnoreGroup` of the pacman configuration at `path` (usually
+    /// [`DEFAULT_PACMAN_CONF`](crate::DEFAULT_PACMAN_CONF)) instead.
+    ///
+    /// Pacman's own defaults are used for what it doesn't set.
+    pub fn from_pacman_conf(path: &Path) -> error::Result<Self> {
+        let conf = PacmanConf::read(path)?;
+        Ok(Options {
+            dirs: if conf.cache_dirs.is_empty() {
+                vec![PathBuf::from(DEFAULT_CACHEDIR)]
+            } else {
+                conf.cache_dirs
+            },
+            dbpath: conf.dbpath.unwrap_or_else(|| PathBuf::from(DEFAULT_DBPATH)),
+            ignore_pkgs: conf.ignore_pkgs,
+            ignore_groups: conf.ignore_groups,
+            ..Options::default()
+        })
+    }
+}
+
 /// How much interraction from the user is needed.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
 pub enum AutoConfirmLevel {
@@ -52,6 +157,23 @@ impl AutoConfirmLevel {
     }
 }
 
+impl FromStr for AutoConfirmLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nothing" => Ok(AutoConfirmLevel::Nothing),
+            "removal" => Ok(AutoConfirmLevel::Removal),
+            "ambiguities" => Ok(AutoConfirmLevel::Ambiguities),
+            "everything" => Ok(AutoConfirmLevel::Everything),
+            _ => Err(format!(
+                "unknown confirm level `{}`, expected `nothing`, `removal`, `ambiguities` or `everything`",
+                s
+            )),
+        }
+    }
+}
+
 impl fmt::Display for AutoConfirmLevel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use AutoConfirmLevel::*;
@@ -64,3 +186,155 @@ impl fmt::Display for AutoConfirmLevel {
         }
     }
 }
+
+/// Which date is shown next to the versions of a package.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum DisplayedTime {
+    /// Creation time of the file, or its modification time if the filesystem doesn't support
+    /// it (NFS, some overlayfs setups, ...)
+    #[default]
+    Created,
+    /// Modification time of the file
+    Modified,
+    /// `builddate` from the `.PKGINFO` when it has been read, or else the modification time
+    Built,
+}
+
+impl FromStr for DisplayedTime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(DisplayedTime::Created),
+            "modified" => Ok(DisplayedTime::Modified),
+            "built" => Ok(DisplayedTime::Built),
+            _ => Err(format!(
+                "unknown time `{}`, expected `created`, `modified` or `built`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for DisplayedTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisplayedTime::Created => write!(f, "created"),
+            DisplayedTime::Modified => write!(f, "modified"),
+            DisplayedTime::Built => write!(f, "built"),
+        }
+    }
+}
+
+/// How the cleaning decisions are printed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// Lists for humans
+    #[default]
+    Text,
+    /// One JSON document on stdout, everything else on stderr
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("unknown format `{}`, expected `text` or `json`", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputFormat::Text => write!(f, "text"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Which symbolic links to directories are followed when scanning subdirectories, see
+/// [`Options::max_depth`].
+///
+/// Links to package files are always read.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum FollowSymlinks {
+    /// Links to directories are skipped
+    #[default]
+    Never,
+    /// Links to directories are scanned like directories, each directory being scanned once
+    Always,
+}
+
+impl FromStr for FollowSymlinks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(FollowSymlinks::Never),
+            "always" => Ok(FollowSymlinks::Always),
+            _ => Err(format!(
+                "unknown symlink policy `{}`, expected `never` or `always`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FollowSymlinks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FollowSymlinks::Never => write!(f, "never"),
+            FollowSymlinks::Always => write!(f, "always"),
+        }
+    }
+}
+
+/// Which versions are compared together when scanning subdirectories.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum Grouping {
+    /// All the versions of a package are compared, whatever their directory
+    #[default]
+    Global,
+    /// Each directory is cleaned on its own, as a build directory per repository or
+    /// architecture
+    PerDirectory,
+}
+
+impl Grouping {
+    /// The group of the package file at `path` : its directory with `PerDirectory`.
+    pub(crate) fn group_of(&self, path: &Path) -> Option<PathBuf> {
+        match self {
+            Grouping::Global => None,
+            Grouping::PerDirectory => path.parent().map(Path::to_path_buf),
+        }
+    }
+}
+
+impl FromStr for Grouping {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "global" => Ok(Grouping::Global),
+            "per-directory" => Ok(Grouping::PerDirectory),
+            _ => Err(format!(
+                "unknown grouping `{}`, expected `global` or `per-directory`",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Grouping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Grouping::Global => write!(f, "global"),
+            Grouping::PerDirectory => write!(f, "per-directory"),
+        }
+    }
+}
@@ -1,121 +1,301 @@
 use std::cmp::Ordering;
-use std::fmt::Debug;
-use std::iter::{once, Iterator};
-use std::ops::{Deref, DerefMut};
-use std::path::PathBuf;
+use std::error;
+use std::fmt;
+use std::fs::metadata;
+use std::io;
+use std::iter::once;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use version_compare::{Cmp, Version};
+use crate::compare::VersionComparator;
+use crate::error::{Error, Result};
+use crate::options::DisplayedTime;
+#[cfg(feature = "pkginfo")]
+use crate::pkginfo::PkgInfo;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PackageParseError {
     NoPackageName,
     EmptyPathOrRoot,
-    CouldntParsePkgver(String),
+    /// The file name and the `.PKGINFO` don't agree on the name or version.
+    #[cfg(feature = "pkginfo")]
+    MetadataMismatch {
+        /// `(name, pkgver)` from the file name
+        file_name: (String, String),
+        /// `(pkgname, pkgver)` from the `.PKGINFO`
+        pkginfo: (String, String),
+    },
 }
 
+impl fmt::Display for PackageParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackageParseError::NoPackageName => {
+                write!(f, "no package name and version in the file name")
+            }
+            PackageParseError::EmptyPathOrRoot => write!(f, "empty path or root"),
+            #[cfg(feature = "pkginfo")]
+            PackageParseError::MetadataMismatch { file_name, pkginfo } => write!(
+                f,
+                "named after `{}` `{}` but its `.PKGINFO` says `{}` `{}`",
+                file_name.0, file_name.1, pkginfo.0, pkginfo.1
+            ),
+        }
+    }
+}
+
+impl error::Error for PackageParseError {}
+
 #[derive(Debug)]
-pub struct Package<'a> {
-    pub path: &'a PathBuf,
-    pub name: &'a str,
-    pub pkgverstr: &'a str,
-    pub pkgver: Version<'a>,
+pub struct Package {
+    pub path: PathBuf,
+    pub name: String,
+    /// Full version : `[epoch:]pkgver-pkgrel`
+    pub pkgver: String,
+    /// Its signature file, if any (same path with `.sig` appended)
+    pub sig: Option<PathBuf>,
+    /// The following are only known when read from the `.PKGINFO`.
+    pub pkgbase: Option<String>,
+    pub arch: Option<String>,
+    /// Unix timestamp of the build
+    pub builddate: Option<i64>,
+    /// Installed size in bytes
+    pub size: Option<u64>,
+    /// Identical copies of this file found in the following directories, they go wherever it
+    /// goes
+    pub duplicates: Vec<Package>,
 }
 
-impl<'a> Package<'a> {
-    pub fn from_path(path: &'a PathBuf) -> Result<Self, (PackageParseError, PathBuf)> {
-        match path.file_name().map(|file_name| {
-            extract_name_version(file_name.to_str().unwrap()).map(|(n, v)| (n, v, Version::from(v)))
-        }) {
-            Some(Ok((name, pkgverstr, Some(pkgver)))) => Ok(Package {
-                name,
-                path,
-                pkgverstr,
-                pkgver,
+impl Package {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let parse_error = |kind| Error::Parse {
+            path: path.to_path_buf(),
+            kind,
+        };
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| parse_error(PackageParseError::EmptyPathOrRoot))?;
+        let (name, pkgver) = file_name
+            .to_str()
+            .ok_or(PackageParseError::NoPackageName)
+            .and_then(extract_name_version)
+            .map_err(parse_error)?;
+
+        Ok(Package {
+            path: path.to_path_buf(),
+            name: name.to_string(),
+            pkgver: pkgver.to_string(),
+            sig: None,
+            pkgbase: None,
+            arch: None,
+            builddate: None,
+            size: None,
+            duplicates: Vec::new(),
+        })
+    }
+
+    /// Builds the package from the `.PKGINFO` inside its archive, falling back to the file name
+    /// if it isn't an archive.
+    ///
+    /// Files named after another package or version than their metadata are rejected with
+    /// [`PackageParseError::MetadataMismatch`], unreadable archives with [`Error::Io`].
+    #[cfg(feature = "pkginfo")]
+    pub fn from_archive(path: &Path) -> Result<Self> {
+        let from_name = Package::from_path(path);
+
+        let info = match PkgInfo::read(path) {
+            Ok(Some(info)) => info,
+            Ok(None) => return from_name,
+            Err(err) => return Err(Error::io(path, err)),
+        };
+
+        match from_name {
+            Ok(p) if p.name != info.pkgname || p.pkgver != info.pkgver => Err(Error::Parse {
+                path: path.to_path_buf(),
+                kind: PackageParseError::MetadataMismatch {
+                    file_name: (p.name, p.pkgver),
+                    pkginfo: (info.pkgname, info.pkgver),
+                },
+            }),
+            _ => Ok(Package {
+                path: path.to_path_buf(),
+                name: info.pkgname,
+                pkgver: info.pkgver,
+                sig: None,
+                pkgbase: info.pkgbase,
+                arch: info.arch,
+                builddate: info.builddate,
+                size: info.size,
+                duplicates: Vec::new(),
             }),
-            Some(Ok((_, pkgverstr, None))) => Err((
-                PackageParseError::CouldntParsePkgver(pkgverstr.to_string()),
-                path.clone(),
-            )),
-            Some(Err((e, _))) => Err((e, path.clone())),
-            None => Err((PackageParseError::EmptyPathOrRoot, path.clone())),
         }
     }
 
-    pub fn compare_versions(a: &Package, b: &Package) -> Ordering {
-        match Version::compare(&a.pkgver, &b.pkgver) {
-            Cmp::Eq => {
-                // TODO: log_lvl
-                eprintln!(
-                    "WWW package `{}` : versions `{}` and `{}` seems to be the same.",
-                    a.name, a.pkgver, b.pkgver
-                );
-                Ordering::Equal
-            }
-            Cmp::Ge | Cmp::Gt => Ordering::Greater,
-            Cmp::Le | Cmp::Lt => Ordering::Less,
-            Cmp::Ne => {
-                eprintln!("WWW package `{}` : versions `{}` and `{}` seems to be different, but we can't compare them.",
-                    a.name,
-                    a.pkgver,
-                    b.pkgver
-                );
-                Ordering::Equal
+    /// The package file and its signature, then its duplicates and their signatures.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        once(self.path.as_path()).chain(self.sig.as_deref()).chain(
+            self.duplicates
+                .iter()
+                .flat_map(|d| once(d.path.as_path()).chain(d.sig.as_deref())),
+        )
+    }
+
+    /// When the package was built : its `builddate` if known, or else the modification time of
+    /// the file.
+    pub fn build_time(&self) -> Option<SystemTime> {
+        match self.builddate {
+            Some(builddate) => {
+                UNIX_EPOCH.checked_add(Duration::from_secs(u64::try_from(builddate).ok()?))
             }
+            None => metadata(&self.path).and_then(|m| m.modified()).ok(),
         }
     }
-}
 
-/// Contains a package and its possible ambiguities, the first one having the priority over the
-/// rest.
-/// If two of the rest can be compared, it's not taken into account.
-///
-/// We use a value and an array for it to be easier to handle and might save some memory as most
-/// packages won't even fill the vec.
-pub struct Packages<'a>(Package<'a>, Vec<Package<'a>>);
+    /// Returns the requested date of the package, or its modification time if it isn't
+    /// available, along with which one it is.
+    pub fn displayed_time(&self, kind: DisplayedTime) -> io::Result<(DisplayedTime, SystemTime)> {
+        let metadata = metadata(&self.path)?;
+        let fallback = || Ok((DisplayedTime::Modified, metadata.modified()?));
 
-impl<'a> Packages<'a> {
-    pub fn new(p: Package<'a>) -> Self {
-        Packages(p, Vec::with_capacity(0))
+        match kind {
+            DisplayedTime::Created => match metadata.created() {
+                Ok(created) => Ok((DisplayedTime::Created, created)),
+                Err(_) => fallback(),
+            },
+            DisplayedTime::Modified => fallback(),
+            DisplayedTime::Built => match self.builddate.and_then(|_| self.build_time()) {
+                Some(built) => Ok((DisplayedTime::Built, built)),
+                None => fallback(),
+            },
+        }
     }
 
-    pub fn get_name(&self) -> &str {
-        self.0.name
+    /// Compares the versions using the given comparator, `None` meaning they can't be
+    /// ordered.
+    pub fn compare_versions<C: VersionComparator>(
+        cmp: &C,
+        a: &Package,
+        b: &Package,
+    ) -> Option<Ordering> {
+        let ord = cmp.compare(&a.pkgver, &b.pkgver);
+        match ord {
+            // TODO: log_lvl
+            Some(Ordering::Equal) => eprintln!(
+                "WWW package `{}` : versions `{}` and `{}` seems to be the same.",
+                a.name, a.pkgver, b.pkgver
+            ),
+            None => eprintln!(
+                "WWW package `{}` : versions `{}` and `{}` seems to be different, but `{}` can't compare them.",
+                a.name,
+                a.pkgver,
+                b.pkgver,
+                cmp.name()
+            ),
+            _ => (),
+        }
+        ord
     }
+}
 
-    pub fn has_ambs(&self) -> bool {
-        !self.1.is_empty()
-    }
+/// All the versions of a package, split according to how many of them we keep.
+///
+/// Versions are sorted newest first and those that can't be ordered between each other are
+/// grouped in a tier, which is ambiguous if it crosses the limit of versions to keep.
+pub struct Packages {
+    /// Versions to keep.
+    pub keep: Vec<Package>,
+    /// Versions that can't be ordered around the limit, `slots` of them should be kept.
+    pub ambs: Vec<Package>,
+    /// How many versions of `ambs` to keep.
+    pub slots: usize,
+    /// Versions older than the ones we keep.
+    pub old: Vec<Package>,
+}
 
-    pub fn add_ambiguity(&mut self, p: Package<'a>) {
-        self.1.push(p);
-    }
+impl Packages {
+    /// Sorts `versions` (all from the same package) and keeps the `keep_versions` newest ones.
+    ///
+    /// If `ask_everything` is set, all the versions are considered ambiguous as soon as some
+    /// have to be removed, so the user can check the comparator.
+    pub fn split<C: VersionComparator>(
+        cmp: &C,
+        versions: Vec<Package>,
+        keep_versions: usize,
+        ask_everything: bool,
+    ) -> Self {
+        // Insertion sort : `sort_by` expects a total order, which a comparator that can't
+        // order some versions doesn't provide.
+        let mut sorted: Vec<Package> = Vec::with_capacity(versions.len());
+        for pkg in versions {
+            let i = sorted
+                .iter()
+                .position(|p| cmp.compare(&pkg.pkgver, &p.pkgver) == Some(Ordering::Greater))
+                .unwrap_or(sorted.len());
+            sorted.insert(i, pkg);
+        }
 
-    /// Iterate over the current package and its ambiguities.
-    pub fn into_iter(self) -> impl Iterator<Item = Package<'a>> {
-        let Packages(p, pkgs) = self;
-        once(p).chain(pkgs)
-    }
+        let mut split = Packages {
+            keep: Vec::new(),
+            ambs: Vec::new(),
+            slots: 0,
+            old: Vec::new(),
+        };
 
-    /*
-    pub fn into_vec(self) -> Vec<Package> {
-        let Packages(p, mut pkgs) = self;
-        pkgs.push(p);
-        pkgs
-    }
-    */
-}
+        if ask_everything && keep_versions > 0 && sorted.len() > keep_versions {
+            split.ambs = sorted;
+            split.slots = keep_versions;
+            return split;
+        }
 
-impl<'a> Deref for Packages<'a> {
-    type Target = Package<'a>;
+        let mut tiers: Vec<Vec<Package>> = Vec::new();
+        for pkg in sorted {
+            match tiers.last_mut() {
+                Some(tier)
+                    if Package::compare_versions(cmp, tier.last().unwrap(), &pkg)
+                        != Some(Ordering::Greater) =>
+                {
+                    tier.push(pkg)
+                }
+                _ => tiers.push(vec![pkg]),
+            }
+        }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+        let mut remaining = keep_versions;
+        for mut tier in tiers {
+            if tier.len() <= remaining {
+                remaining -= tier.len();
+                split.keep.append(&mut tier);
+            } else if remaining == 0 {
+                split.old.append(&mut tier);
+            } else {
+                split.slots = remaining;
+                split.ambs = tier;
+                remaining = 0;
+            }
+        }
+
+        split
     }
-}
 
-impl<'a> DerefMut for Packages<'a> {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+    /// Moves the versions for which `is_installed` is true to the ones we keep, whatever their
+    /// age, taking their place among the ambiguities if needed.
+    pub fn keep_installed<F: Fn(&Package) -> bool>(&mut self, is_installed: F) {
+        let (mut installed, old): (Vec<_>, Vec<_>) = self.old.drain(..).partition(&is_installed);
+        self.old = old;
+        self.keep.append(&mut installed);
+
+        let (mut installed, mut ambs): (Vec<_>, Vec<_>) =
+            self.ambs.drain(..).partition(&is_installed);
+        self.slots = self.slots.saturating_sub(installed.len());
+        self.keep.append(&mut installed);
+        if self.slots == 0 {
+            self.old.append(&mut ambs);
+        } else if ambs.len() <= self.slots {
+            self.keep.append(&mut ambs);
+            self.slots = 0;
+        } else {
+            self.ambs = ambs;
+        }
     }
 }
 
@@ -132,7 +312,7 @@ const PARSE_PKG_NAME_REGEX: &str = r"(.*)-([^-]+-[^-]+)-[^-]+.pkg.tar.*";
 /// // assert_eq!(extract_name_version("acpi-1.7-3-x86_64.pkg.tar.zst"), Ok(("acpi", "1.7-3")))
 /// ```
 #[cfg(feature = "regex")]
-fn extract_name_version(file_name: &str) -> Result<(&str, &str), (PackageParseError, String)> {
+fn extract_name_version(file_name: &str) -> std::result::Result<(&str, &str), PackageParseError> {
     use regex::Regex;
     use std::sync::LazyLock;
 
@@ -143,7 +323,7 @@ fn extract_name_version(file_name: &str) -> Result<(&str, &str), (PackageParseEr
     let captures = if let Some(captures) = RE.captures(file_name) {
         captures
     } else {
-        return Err((PackageParseError::NoPackageName, file_name.to_string()));
+        return Err(PackageParseError::NoPackageName);
     };
 
     // Needs to do this jump to get direct access to &str
@@ -154,9 +334,9 @@ fn extract_name_version(file_name: &str) -> Result<(&str, &str), (PackageParseEr
 }
 
 #[cfg(not(feature = "regex"))]
-fn extract_name_version(file_name: &str) -> Result<(&str, &str), (PackageParseError, String)> {
+fn extract_name_version(file_name: &str) -> std::result::Result<(&str, &str), PackageParseError> {
     if file_name.split('-').count() <= 3 {
-        return Err((PackageParseError::NoPackageName, file_name.to_string()));
+        return Err(PackageParseError::NoPackageName);
     }
 
     let mut f_iter = file_name.split('-');
@@ -170,16 +350,85 @@ fn extract_name_version(file_name: &str) -> Result<(&str, &str), (PackageParseEr
     // Checking extension in `.pkg.tar.{algo}` :
     let rest = f_iter.next().unwrap();
     if rest.split('.').count() != 4 {
-        return Err((PackageParseError::NoPackageName, file_name.to_string()));
+        return Err(PackageParseError::NoPackageName);
     }
     let mut rest_iter = rest.split('.').skip(1);
     // let _arch = rest_iter_iter.next().unwrap();
     let pkg = rest_iter.next().unwrap();
     let tar = rest_iter.next().unwrap();
     if !(pkg == "pkg" && tar == "tar") {
-        return Err((PackageParseError::NoPackageName, file_name.to_string()));
+        return Err(PackageParseError::NoPackageName);
     }
     // let _algo = f_iter.next().unwrap();
 
     Ok((name, pkgver))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compare::AlpmComparator;
+
+    fn pkg(pkgver: &str) -> Package {
+        Package::from_path(Path::new(&format!(
+            "/cache/foo-{}-x86_64.pkg.tar.zst",
+            pkgver
+        )))
+        .unwrap()
+    }
+
+    fn versions(pkgs: &[Package]) -> Vec<&str> {
+        pkgs.iter().map(|p| &p.pkgver[..]).collect()
+    }
+
+    #[test]
+    fn keep_installed_old_version() {
+        let mut split = Packages::split(
+            &AlpmComparator,
+            vec![pkg("1.0-1"), pkg("3.0-1"), pkg("2.0-1")],
+            1,
+            false,
+        );
+        split.keep_installed(|p| p.pkgver == "1.0-1");
+        assert_eq!(versions(&split.keep), ["3.0-1", "1.0-1"]);
+        assert_eq!(versions(&split.old), ["2.0-1"]);
+    }
+
+    #[test]
+    fn keep_installed_nothing_installed() {
+        let mut split =
+            Packages::split(&AlpmComparator, vec![pkg("1.0-1"), pkg("2.0-1")], 1, false);
+        split.keep_installed(|_| false);
+        assert_eq!(versions(&split.keep), ["2.0-1"]);
+        assert_eq!(versions(&split.old), ["1.0-1"]);
+    }
+
+    #[test]
+    fn keep_installed_takes_an_ambiguous_slot() {
+        let mut split = Packages {
+            keep: Vec::new(),
+            ambs: vec![pkg("1.0a-1"), pkg("1.0b-1"), pkg("1.0c-1")],
+            slots: 1,
+            old: Vec::new(),
+        };
+        split.keep_installed(|p| p.pkgver == "1.0b-1");
+        assert_eq!(versions(&split.keep), ["1.0b-1"]);
+        assert_eq!(split.slots, 0);
+        assert!(split.ambs.is_empty());
+        assert_eq!(versions(&split.old), ["1.0a-1", "1.0c-1"]);
+    }
+
+    #[test]
+    fn keep_installed_resolves_the_ambiguity() {
+        let mut split = Packages {
+            keep: Vec::new(),
+            ambs: vec![pkg("1.0a-1"), pkg("1.0b-1"), pkg("1.0c-1")],
+            slots: 2,
+            old: Vec::new(),
+        };
+        split.keep_installed(|p| p.pkgver == "1.0c-1");
+        assert_eq!(split.slots, 1);
+        assert_eq!(versions(&split.keep), ["1.0c-1"]);
+        assert_eq!(versions(&split.ambs), ["1.0a-1", "1.0b-1"]);
+    }
+}
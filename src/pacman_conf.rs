@@ -0,0 +1,260 @@
+use std::fs::{read_dir, read_to_string};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::glob::{glob_match, is_pattern};
+
+/// Default path of pacman's configuration.
+pub const DEFAULT_PACMAN_CONF: &str = "/etc/pacman.conf";
+
+/// Default pacman cache directory, as `CacheDir` in `pacman.conf`.
+pub const DEFAULT_CACHEDIR: &str = "/var/cache/pacman/pkg/";
+
+/// How deep `Include` directives are followed, to stop include loops.
+const MAX_INCLUDE_DEPTH: usize = 10;
+
+/// The settings of the `[options]` section of `pacman.conf` that matter to clean the cache.
+///
+/// `Include` directives are followed in the `[options]` section, the other sections (the
+/// repositories and their mirror lists) are skipped.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use remove_old_arch_pkgs::{PacmanConf, DEFAULT_PACMAN_CONF};
+///
+/// let conf = PacmanConf::read(Path::new(DEFAULT_PACMAN_CONF)).unwrap();
+/// for dir in &conf.cache_dirs {
+///     println!("{}", dir.display());
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PacmanConf {
+    /// `CacheDir` entries, in order
+    pub cache_dirs: Vec<PathBuf>,
+    /// `DBPath`, if set
+    pub dbpath: Option<PathBuf>,
+    /// `IgnorePkg` patterns
+    pub ignore_pkgs: Vec<String>,
+    /// `IgnoreGroup` patterns
+    pub ignore_groups: Vec<String>,
+}
+
+impl PacmanConf {
+    /// Reads the configuration at `path` (usually [`DEFAULT_PACMAN_CONF`]) and the files it
+    /// includes.
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut conf = PacmanConf::default();
+        conf.read_file(path, None, 0)?;
+        Ok(conf)
+    }
+
+    /// Reads `path`, whose content starts in `section`.
+    fn read_file(&mut self, path: &Path, section: Option<&str>, depth: usize) -> Result<()> {
+        let content = read_to_string(path).map_err(|err| Error::io(path, err))?;
+        let invalid = |n: usize, msg: &str| {
+            Error::io(
+                path,
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {} : {}", n + 1, msg),
+                ),
+            )
+        };
+
+        let mut section = section.map(str::to_string);
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                let name = name
+                    .strip_suffix(']')
+                    .ok_or_else(|| invalid(n, "section name without closing `]`"))?;
+                section = Some(name.to_string());
+                continue;
+            }
+            if section.as_deref() != Some("options") {
+                continue;
+            }
+
+            // Flags such as `Color` have no value.
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let values = value.split_whitespace().map(str::to_string);
+            match key.trim() {
+                "CacheDir" => self.cache_dirs.extend(values.map(PathBuf::from)),
+                // Pacman keeps the first one.
+                "DBPath" if self.dbpath.is_none() => self.dbpath = Some(PathBuf::from(value)),
+                "IgnorePkg" => self.ignore_pkgs.extend(values),
+                "IgnoreGroup" => self.ignore_groups.extend(values),
+                "Include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(invalid(n, "too many nested includes"));
+                    }
+                    for included in include_paths(Path::new(value))
+                        .map_err(|err| Error::io(Path::new(value), err))?
+                    {
+                        self.read_file(&included, section.as_deref(), depth + 1)?;
+                    }
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The files matching the `Include` value `pattern`, sorted : a pattern that matches nothing is
+/// fine, a missing file given without wildcard isn't.
+fn include_paths(pattern: &Path) -> io::Result<Vec<PathBuf>> {
+    let (parent, file_name) = match (pattern.parent(), pattern.file_name()) {
+        (Some(parent), Some(file_name)) => (parent, file_name.to_string_lossy()),
+        _ => return Ok(vec![pattern.to_path_buf()]),
+    };
+    if !is_pattern(&file_name) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+
+    let mut paths = Vec::new();
+    let entries = match read_dir(parent) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(paths),
+        Err(err) => return Err(err),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let matches = path
+            .file_name()
+            .is_some_and(|name| glob_match(&file_name, &name.to_string_lossy()));
+        if matches && path.is_file() {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all, write};
+    use std::process;
+
+    /// Writes `files` (relative path, content) in a new directory, `{dir}` in a content being
+    /// replaced with the directory, and reads its `pacman.conf`.
+    fn read_conf(test: &str, files: &[(&str, &str)]) -> Result<PacmanConf> {
+        let dir = std::env::temp_dir().join(format!(
+            "remove_old_arch_pkgs-pacman-conf-{}-{}",
+            test,
+            process::id()
+        ));
+        for (path, content) in files {
+            let path = dir.join(path);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, content.replace("{dir}", &dir.to_string_lossy())).unwrap();
+        }
+        let conf = PacmanConf::read(&dir.join("pacman.conf"));
+        remove_dir_all(&dir).unwrap();
+        conf
+    }
+
+    #[test]
+    fn includes() {
+        let conf = read_conf(
+            "includes",
+            &[
+                (
+                    "pacman.conf",
+                    "[options]\nCacheDir = /var/cache/pacman/pkg/\nIgnorePkg = linux linux-headers\n\
+                     Include = {dir}/conf.d/*.conf\nInclude = {dir}/none.d/*.conf\n\
+                     \n[core]\nInclude = /etc/pacman.d/mirrorlist\n",
+                ),
+                (
+                    "conf.d/nas.conf",
+                    "# Extra cache\nCacheDir = /mnt/nas/pkg/\nIgnoreGroup = gnome\n",
+                ),
+                ("conf.d/local.conf", "CacheDir = /srv/pkg/\n"),
+                ("conf.d/local.conf.bak", "CacheDir = /old/pkg/\n"),
+            ],
+        )
+        .unwrap();
+
+        // The matching files are read in order, a pattern matching nothing is fine.
+        assert_eq!(
+            conf.cache_dirs,
+            [
+                Path::new("/var/cache/pacman/pkg/"),
+                Path::new("/srv/pkg/"),
+                Path::new("/mnt/nas/pkg/")
+            ]
+        );
+        assert_eq!(conf.ignore_pkgs, ["linux", "linux-headers"]);
+        assert_eq!(conf.ignore_groups, ["gnome"]);
+    }
+
+    #[test]
+    fn missing_include() {
+        let conf = read_conf(
+            "missing-include",
+            &[("pacman.conf", "[options]\nInclude = {dir}/missing.conf\n")],
+        );
+        assert!(matches!(conf, Err(Error::Io { path, .. }) if path.ends_with("missing.conf")));
+    }
+
+    #[test]
+    fn include_loop() {
+        let conf = read_conf(
+            "include-loop",
+            &[
+                ("pacman.conf", "[options]\nInclude = {dir}/loop.conf\n"),
+                (
+                    "loop.conf",
+                    "CacheDir = /loop/\nInclude = {dir}/loop.conf\n",
+                ),
+            ],
+        );
+        match conf {
+            Err(Error::Io { path, source }) => {
+                assert!(path.ends_with("loop.conf"));
+                assert!(source.to_string().contains("too many nested includes"));
+            }
+            other => panic!("expected the include loop to be refused, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn other_sections() {
+        let conf = read_conf(
+            "other-sections",
+            &[(
+                "pacman.conf",
+                "CacheDir = /before/\n[options]\nColor\nCacheDir = /cache/\n\
+                 [custom]\nCacheDir = /custom/\nInclude = {dir}/missing.conf\n\
+                 [options]\nIgnorePkg = foo\n",
+            )],
+        )
+        .unwrap();
+        assert_eq!(conf.cache_dirs, [Path::new("/cache/")]);
+        assert_eq!(conf.ignore_pkgs, ["foo"]);
+    }
+
+    #[test]
+    fn first_dbpath() {
+        let conf = read_conf(
+            "first-dbpath",
+            &[
+                (
+                    "pacman.conf",
+                    "[options]\nDBPath = /first/db/\nInclude = {dir}/db.conf\n",
+                ),
+                ("db.conf", "DBPath = /second/db/\n"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(conf.dbpath.as_deref(), Some(Path::new("/first/db/")));
+    }
+}
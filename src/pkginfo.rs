@@ -0,0 +1,108 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+/// Uncompressed tar archives have it at offset 257.
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+
+/// Metadata of a package, as found in the `.PKGINFO` file at the root of its archive.
+#[derive(Debug, Clone, Default)]
+pub struct PkgInfo {
+    pub pkgname: String,
+    pub pkgbase: Option<String>,
+    /// Full version : `[epoch:]pkgver-pkgrel`
+    pub pkgver: String,
+    pub arch: Option<String>,
+    /// Unix timestamp of the build
+    pub builddate: Option<i64>,
+    /// Installed size in bytes
+    pub size: Option<u64>,
+}
+
+impl PkgInfo {
+    /// Reads the `.PKGINFO` from the package archive at `path`.
+    ///
+    /// The compression is guessed from the content, not the file name, so renamed archives are
+    /// read as well.
+    /// Returns `Ok(None)` if the file isn't an archive or doesn't start with a `.PKGINFO`.
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let archive = match decompress(File::open(path)?)? {
+            Some(archive) => archive,
+            None => return Ok(None),
+        };
+
+        for entry in tar::Archive::new(archive).entries()? {
+            let mut entry = entry?;
+            let entry_path = entry.path()?;
+            if entry_path == Path::new(".PKGINFO") {
+                let mut content = String::new();
+                entry.read_to_string(&mut content)?;
+                return Ok(PkgInfo::parse(&content));
+            } else if !entry_path.to_string_lossy().starts_with('.') {
+                // The metadata files come first, no need to decompress the whole package.
+                break;
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Parses the `key = value` lines of a `.PKGINFO`, `None` if `pkgname` or `pkgver` is
+    /// missing.
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut pkgname = None;
+        let mut info = PkgInfo::default();
+
+        for line in content.lines().filter(|l| !l.starts_with('#')) {
+            let (key, value) = match line.split_once(" = ") {
+                Some(kv) => kv,
+                None => continue,
+            };
+            match key {
+                "pkgname" => pkgname = Some(value.to_string()),
+                "pkgbase" => info.pkgbase = Some(value.to_string()),
+                "pkgver" => info.pkgver = value.to_string(),
+                "arch" => info.arch = Some(value.to_string()),
+                "builddate" => info.builddate = value.parse().ok(),
+                "size" => info.size = value.parse().ok(),
+                _ => (),
+            }
+        }
+
+        if info.pkgver.is_empty() {
+            return None;
+        }
+        info.pkgname = pkgname?;
+        Some(info)
+    }
+}
+
+/// Returns a reader on the decompressed tar archive, or `None` if `file` doesn't look like one.
+fn decompress(mut file: File) -> io::Result<Option<Box<dyn Read>>> {
+    let mut magic = Vec::with_capacity(TAR_MAGIC_OFFSET + TAR_MAGIC.len());
+    (&mut file)
+        .take((TAR_MAGIC_OFFSET + TAR_MAGIC.len()) as u64)
+        .read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let archive: Box<dyn Read> = if magic.starts_with(ZSTD_MAGIC) {
+        Box::new(zstd::stream::read::Decoder::new(file)?)
+    } else if magic.starts_with(XZ_MAGIC) {
+        Box::new(xz2::read::XzDecoder::new(file))
+    } else if magic.starts_with(GZIP_MAGIC) {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else if magic.starts_with(BZIP2_MAGIC) {
+        Box::new(bzip2::read::BzDecoder::new(file))
+    } else if magic.get(TAR_MAGIC_OFFSET..) == Some(TAR_MAGIC) {
+        Box::new(file)
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some(archive))
+}
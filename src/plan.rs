@@ -0,0 +1,288 @@
+use std::fmt;
+use std::fs::metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::explain::Explanation;
+use crate::package::{Package, PackageParseError};
+
+/// What cleaning the cache would do, see [`plan`](crate::plan) and [`apply`](crate::apply).
+#[derive(Debug, Default)]
+pub struct CleanupPlan {
+    /// Packages to keep
+    pub kept: Vec<Package>,
+    /// Packages to remove, along with their signature
+    pub old: Vec<Package>,
+    /// Files left alone
+    pub ignored: Vec<IgnoredFile>,
+    /// Versions the plan can't choose between, see [`CleanupPlan::resolve`].
+    /// They are left alone if they aren't resolved.
+    pub ambiguities: Vec<Ambiguity>,
+    /// Why each package is kept, removed or ambiguous, if asked for with
+    /// [`Options::explain`](crate::Options::explain)
+    pub explanations: Vec<Explanation>,
+    /// The scanned directories, in the order they were given
+    pub dirs: Vec<PathBuf>,
+}
+
+impl CleanupPlan {
+    /// Every file to remove : the old packages and their signatures, sorted.
+    pub fn files_to_remove(&self) -> Vec<&Path> {
+        let mut files: Vec<&Path> = self.old.iter().flat_map(Package::files).collect();
+        files.sort();
+        files
+    }
+
+    /// The scanned directory `path` comes from.
+    pub fn source_dir(&self, path: &Path) -> Option<&Path> {
+        self.dirs
+            .iter()
+            .filter(|dir| path.starts_with(dir))
+            .max_by_key(|dir| dir.components().count())
+            .map(PathBuf::as_path)
+    }
+
+    /// Settles `amb` by keeping the versions at the indices in `choice` and removing the
+    /// others, or ignoring all of them if there is no choice.
+    pub fn resolve(&mut self, amb: Ambiguity, choice: Option<&[usize]>) {
+        match choice {
+            Some(choice) => amb.versions.into_iter().enumerate().for_each(|(i, p)| {
+                if choice.contains(&i) {
+                    self.kept.push(p)
+                } else {
+                    self.old.push(p)
+                }
+            }),
+            None => {
+                for p in amb.versions {
+                    self.ignored.push(IgnoredFile {
+                        path: p.path,
+                        reason: IgnoreReason::Ambiguous,
+                    });
+                    if let Some(sig) = p.sig {
+                        self.ignored.push(IgnoredFile {
+                            path: sig,
+                            reason: IgnoreReason::Ambiguous,
+                        });
+                    }
+                }
+            }
+        }
+        self.sort();
+    }
+
+    /// The plan as a JSON document : the kept packages, the files to remove, the ignored files
+    /// with the reason why and the unresolved ambiguities, each file with the directory it
+    /// comes from.
+    pub fn to_json(&self) -> String {
+        let report = PlanReport {
+            kept: self
+                .kept
+                .iter()
+                .map(|p| PackageReport::new(p, self))
+                .collect(),
+            removed: self
+                .files_to_remove()
+                .into_iter()
+                .map(|path| FileReport::new(path, self))
+                .collect(),
+            ignored: self
+                .ignored
+                .iter()
+                .map(|f| IgnoredReport {
+                    path: &f.path,
+                    reason: f.reason.to_string(),
+                })
+                .collect(),
+            ambiguities: self
+                .ambiguities
+                .iter()
+                .map(|amb| AmbiguityReport {
+                    name: &amb.name,
+                    slots: amb.slots,
+                    versions: amb
+                        .versions
+                        .iter()
+                        .map(|p| PackageReport::new(p, self))
+                        .collect(),
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&report).expect("plan report can't be serialized")
+    }
+
+    // Ideally I might not sort them here as it is purely aesthetical, but for such a simple prog,
+    // it's okay.
+    pub(crate) fn sort(&mut self) {
+        self.kept.sort_by(|a, b| a.path.cmp(&b.path));
+        self.old.sort_by(|a, b| a.path.cmp(&b.path));
+        self.ignored.sort_by(|a, b| a.path.cmp(&b.path));
+        self.ambiguities.sort_by(|a, b| a.name.cmp(&b.name));
+        self.explanations.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+}
+
+/// Versions of a package that can't be ordered around the limit of versions to keep.
+#[derive(Debug)]
+pub struct Ambiguity {
+    pub name: String,
+    /// The most recently built first
+    pub versions: Vec<Package>,
+    /// How many of the versions should be kept
+    pub slots: usize,
+}
+
+impl Ambiguity {
+    /// The most recently built versions.
+    pub fn default_choice(&self) -> Vec<usize> {
+        (0..self.slots).collect()
+    }
+}
+
+/// A file we don't touch.
+#[derive(Debug)]
+pub struct IgnoredFile {
+    pub path: PathBuf,
+    pub reason: IgnoreReason,
+}
+
+#[derive(Debug)]
+pub enum IgnoreReason {
+    /// The file isn't a package we understand
+    NotAPackage(PackageParseError),
+    /// The file couldn't be read to tell whether it is a package
+    Unreadable(io::Error),
+    /// The file name and the `.PKGINFO` don't agree on the name or version
+    #[cfg(feature = "pkginfo")]
+    MetadataMismatch {
+        /// `(name, pkgver)` from the file name
+        file_name: (String, String),
+        /// `(pkgname, pkgver)` from the `.PKGINFO`
+        pkginfo: (String, String),
+    },
+    /// The version is ambiguous and the user chose to leave it
+    Ambiguous,
+    /// Signature of a file that isn't a package
+    OrphanSignature,
+    /// The package matches `IgnorePkg` in `pacman.conf`, see
+    /// [`Options::ignore_pkgs`](crate::Options::ignore_pkgs)
+    IgnorePkg,
+    /// The package is in this group, which matches `IgnoreGroup` in `pacman.conf`
+    IgnoreGroup(String),
+}
+
+/// `io::Error` isn't `Clone` : the copy of an `Unreadable` keeps only its kind and message.
+impl Clone for IgnoreReason {
+    fn clone(&self) -> Self {
+        match self {
+            IgnoreReason::NotAPackage(e) => IgnoreReason::NotAPackage(e.clone()),
+            IgnoreReason::Unreadable(e) => {
+                IgnoreReason::Unreadable(io::Error::new(e.kind(), e.to_string()))
+            }
+            #[cfg(feature = "pkginfo")]
+            IgnoreReason::MetadataMismatch { file_name, pkginfo } => {
+                IgnoreReason::MetadataMismatch {
+                    file_name: file_name.clone(),
+                    pkginfo: pkginfo.clone(),
+                }
+            }
+            IgnoreReason::Ambiguous => IgnoreReason::Ambiguous,
+            IgnoreReason::OrphanSignature => IgnoreReason::OrphanSignature,
+            IgnoreReason::IgnorePkg => IgnoreReason::IgnorePkg,
+            IgnoreReason::IgnoreGroup(group) => IgnoreReason::IgnoreGroup(group.clone()),
+        }
+    }
+}
+
+impl fmt::Display for IgnoreReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IgnoreReason::NotAPackage(e) => write!(f, "not a package : {}", e),
+            IgnoreReason::Unreadable(e) => write!(f, "unreadable : {}", e),
+            #[cfg(feature = "pkginfo")]
+            IgnoreReason::MetadataMismatch { file_name, pkginfo } => write!(
+                f,
+                "metadata mismatch : named after `{}` `{}` but its `.PKGINFO` says `{}` `{}`",
+                file_name.0, file_name.1, pkginfo.0, pkginfo.1
+            ),
+            IgnoreReason::Ambiguous => write!(f, "ambiguous version"),
+            IgnoreReason::OrphanSignature => write!(f, "signature without package"),
+            IgnoreReason::IgnorePkg => write!(f, "ignored by pacman, in `IgnorePkg`"),
+            IgnoreReason::IgnoreGroup(group) => {
+                write!(
+                    f,
+                    "ignored by pacman, its group `{}` is in `IgnoreGroup`",
+                    group
+                )
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PlanReport<'a> {
+    kept: Vec<PackageReport<'a>>,
+    removed: Vec<FileReport<'a>>,
+    ignored: Vec<IgnoredReport<'a>>,
+    ambiguities: Vec<AmbiguityReport<'a>>,
+}
+
+#[derive(Serialize)]
+struct PackageReport<'a> {
+    name: &'a str,
+    version: &'a str,
+    path: &'a Path,
+    dir: Option<&'a Path>,
+    /// Size of the file in bytes, `null` if it can't be read
+    size: Option<u64>,
+    /// Identical copies in the following directories
+    duplicates: Vec<FileReport<'a>>,
+}
+
+impl<'a> PackageReport<'a> {
+    fn new(p: &'a Package, plan: &'a CleanupPlan) -> Self {
+        PackageReport {
+            name: &p.name,
+            version: &p.pkgver,
+            path: &p.path,
+            dir: plan.source_dir(&p.path),
+            size: metadata(&p.path).map(|m| m.len()).ok(),
+            duplicates: p
+                .duplicates
+                .iter()
+                .map(|d| FileReport::new(&d.path, plan))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct FileReport<'a> {
+    path: &'a Path,
+    dir: Option<&'a Path>,
+}
+
+impl<'a> FileReport<'a> {
+    fn new(path: &'a Path, plan: &'a CleanupPlan) -> Self {
+        FileReport {
+            path,
+            dir: plan.source_dir(path),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct IgnoredReport<'a> {
+    path: &'a Path,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct AmbiguityReport<'a> {
+    name: &'a str,
+    slots: usize,
+    /// The most recently built first
+    versions: Vec<PackageReport<'a>>,
+}
@@ -0,0 +1,200 @@
+use std::fs::{metadata, read_to_string, write};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{absolute, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::is_package_name;
+use crate::plan::CleanupPlan;
+
+/// Format of the plan files, bumped on incompatible changes.
+const PLAN_FILE_VERSION: u32 = 2;
+
+/// The files a cleaning would remove, saved to be reviewed then applied later, maybe by
+/// another user.
+///
+/// The size and modification time of each file are recorded to make sure the cache hasn't
+/// changed in between, see [`PlanFile::changed_files`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanFile {
+    pub version: u32,
+    /// Unix timestamp of the plan
+    pub created: u64,
+    /// The cleaned directories, which have to be among the ones given to apply the plan
+    pub dirs: Vec<PathBuf>,
+    pub files: Vec<PlannedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    /// Modification time, seconds and nanoseconds since the Unix epoch
+    pub mtime: i64,
+    pub mtime_nsec: i64,
+}
+
+impl PlanFile {
+    /// Records the files to remove of `plan`, a plan of `dirs`, with absolute paths.
+    pub fn new(plan: &CleanupPlan, dirs: &[PathBuf]) -> io::Result<Self> {
+        let mut files = Vec::new();
+        for path in plan.files_to_remove() {
+            let metadata = metadata(path)?;
+            files.push(PlannedFile {
+                path: absolute(path)?,
+                size: metadata.len(),
+                mtime: metadata.mtime(),
+                mtime_nsec: metadata.mtime_nsec(),
+            });
+        }
+
+        Ok(PlanFile {
+            version: PLAN_FILE_VERSION,
+            created: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            dirs: dirs.iter().map(absolute).collect::<io::Result<_>>()?,
+            files,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        write(path, json + "\n")
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let plan: PlanFile = serde_json::from_str(&read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if plan.version != PLAN_FILE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "plan file version {} isn't supported, expected {}",
+                    plan.version, PLAN_FILE_VERSION
+                ),
+            ));
+        }
+        Ok(plan)
+    }
+
+    /// Fails if the plan is for a directory outside of the cache directories `allowed`, or on
+    /// the first file that isn't named like a package or a signature or isn't inside one of
+    /// `allowed` once symbolic links and `..` are resolved.
+    ///
+    /// The plan may have been written by another user : only whoever applies it says where
+    /// files can be removed, its own directories are just checked against them.
+    pub fn check_files(&self, allowed: &[PathBuf]) -> io::Result<()> {
+        let refuse = |msg: String| Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+        let allowed: Vec<PathBuf> = allowed
+            .iter()
+            .filter_map(|dir| dir.canonicalize().ok())
+            .collect();
+        let is_allowed = |dir: &Path| {
+            dir.canonicalize()
+                .is_ok_and(|dir| allowed.iter().any(|allowed| dir.starts_with(allowed)))
+        };
+
+        if let Some(dir) = self.dirs.iter().find(|dir| !is_allowed(dir)) {
+            return refuse(format!(
+                "the plan is for `{}`, which isn't one of the cache directories, refusing it",
+                dir.display()
+            ));
+        }
+        match self
+            .files
+            .iter()
+            .find(|f| !is_package_name(&f.path) || !f.path.parent().is_some_and(&is_allowed))
+        {
+            Some(foreign) => refuse(format!(
+                "`{}` isn't a package of the cache directories, refusing the plan",
+                foreign.path.display()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// The files that are gone or whose size or modification time changed since the plan was
+    /// made, with what changed.
+    pub fn changed_files(&self) -> Vec<(PathBuf, String)> {
+        self.files
+            .iter()
+            .filter_map(|f| f.change().map(|change| (f.path.clone(), change)))
+            .collect()
+    }
+}
+
+impl PlannedFile {
+    /// What changed since the plan was made, `None` if nothing did.
+    fn change(&self) -> Option<String> {
+        let metadata = match metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(err) => return Some(format!("can't be read : {}", err)),
+        };
+        if metadata.len() != self.size {
+            Some(format!(
+                "size changed from {} to {} bytes",
+                self.size,
+                metadata.len()
+            ))
+        } else if (metadata.mtime(), metadata.mtime_nsec()) != (self.mtime, self.mtime_nsec) {
+            Some("modification time changed".to_string())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{create_dir_all, remove_dir_all};
+    use std::process;
+
+    fn plan(dirs: &[&Path], files: &[&Path]) -> PlanFile {
+        PlanFile {
+            version: PLAN_FILE_VERSION,
+            created: 0,
+            dirs: dirs.iter().map(|d| d.to_path_buf()).collect(),
+            files: files
+                .iter()
+                .map(|f| PlannedFile {
+                    path: f.to_path_buf(),
+                    size: 0,
+                    mtime: 0,
+                    mtime_nsec: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn check_files() {
+        let root =
+            std::env::temp_dir().join(format!("remove_old_arch_pkgs-planfile-{}", process::id()));
+        let cache = root.join("cache");
+        let victim = root.join("victim");
+        create_dir_all(&cache).unwrap();
+        create_dir_all(&victim).unwrap();
+        let allowed = [cache.clone()];
+        let pkg = cache.join("foo-1.0-1-x86_64.pkg.tar.zst");
+        let sig = cache.join("foo-1.0-1-x86_64.pkg.tar.zst.sig");
+        let notes = victim.join("notes.pkg.tar.bak");
+
+        let result = [
+            plan(&[&cache], &[&pkg, &sig]).check_files(&allowed),
+            // The plan can't widen the directories it applies to.
+            plan(&[Path::new("/")], &[&notes]).check_files(&allowed),
+            plan(&[&cache], &[&notes]).check_files(&allowed),
+            plan(&[&cache], &[&cache.join("../victim/notes.pkg.tar.bak")]).check_files(&allowed),
+            plan(&[&cache], &[&cache.join("notes.txt")]).check_files(&allowed),
+        ];
+        remove_dir_all(&root).unwrap();
+
+        assert!(result[0].is_ok());
+        assert!(result[1..].iter().all(|r| r.is_err()));
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::options::{AutoConfirmLevel, DisplayedTime, Options};
+use crate::plan::Ambiguity;
+
+/// Takes the decisions of a cleaning that may need a human.
+pub trait Prompter {
+    /// Which versions of `amb` to keep : `amb.slots` indices in `amb.versions`, or `None` to
+    /// leave all of them alone.
+    fn choose_versions(&mut self, amb: &Ambiguity) -> Result<Option<Vec<usize>>, NoAnswer>;
+
+    /// Whether to go on with removing `files`.
+    fn confirm_removal(&mut self, files: &[&Path]) -> Result<bool, NoAnswer>;
+}
+
+/// No answer could be obtained : closed input, read error, no terminal in non-interactive
+/// mode, ...
+///
+/// The cleaning should be aborted without removing anything.
+#[derive(Debug, Clone)]
+pub struct NoAnswer(pub String);
+
+impl fmt::Display for NoAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no answer : {}", self.0)
+    }
+}
+
+impl Error for NoAnswer {}
+
+/// Asks the user on the terminal, as often as the auto-confirm level requires.
+#[derive(Debug, Clone)]
+pub struct TerminalPrompter {
+    pub auto_confirm_level: AutoConfirmLevel,
+    /// Date shown next to each version
+    pub displayed_time: DisplayedTime,
+    /// Don't wait for an answer if stdin isn't a terminal
+    pub non_interactive: bool,
+}
+
+impl TerminalPrompter {
+    pub fn new(opts: &Options) -> Self {
+        TerminalPrompter {
+            auto_confirm_level: opts.auto_confirm_level,
+            displayed_time: opts.displayed_time,
+            non_interactive: opts.non_interactive,
+        }
+    }
+
+    /// Reads a line from stdin, without the line feed.
+    fn read_line(&self) -> Result<String, NoAnswer> {
+        if self.non_interactive && !io::stdin().is_terminal() {
+            return Err(NoAnswer(
+                "stdin isn't a terminal in non-interactive mode".to_string(),
+            ));
+        }
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => Err(NoAnswer("stdin is closed".to_string())),
+            Ok(_) => Ok(input.trim_end_matches(['\n', '\r']).to_string()),
+            Err(err) => Err(NoAnswer(format!("can't read from stdin : {}", err))),
+        }
+    }
+}
+
+impl Prompter for TerminalPrompter {
+    fn choose_versions(&mut self, amb: &Ambiguity) -> Result<Option<Vec<usize>>, NoAnswer> {
+        let slots = amb.slots;
+        eprintln!(
+            "Package `{}` has {} {}{} :",
+            amb.name,
+            amb.versions.len(),
+            if self.auto_confirm_level.is_everything() {
+                "versions"
+            } else {
+                "ambiguities"
+            },
+            if slots > 1 {
+                format!(" ({} of them can be kept)", slots)
+            } else {
+                String::new()
+            }
+        );
+        amb.versions.iter().enumerate().rev().for_each(|(i, p)| {
+            #[cfg(feature = "chrono")]
+            match p.displayed_time(self.displayed_time) {
+                Ok((kind, time)) => {
+                    let date: chrono::DateTime<chrono::Local> = chrono::DateTime::from(time);
+                    eprintln!("{:2}.\t{}\t({} {})", i, p.pkgver, kind, date.to_rfc2822())
+                }
+                Err(err) => {
+                    eprintln!(
+                        "WWW Can't read the metadata of `{}` : {}",
+                        p.path.display(),
+                        err
+                    );
+                    eprintln!("{:2}.\t{}", i, p.pkgver)
+                }
+            }
+            #[cfg(not(feature = "chrono"))]
+            eprintln!("{:2}.\t{}", i, p.pkgver)
+        });
+
+        if !self.auto_confirm_level.is_at_least_ambiguities() {
+            if slots == 1 {
+                eprintln!("> keeping the most recently built one");
+            } else {
+                eprintln!("> keeping the {} most recently built ones", slots);
+            }
+            Ok(Some(amb.default_choice()))
+        } else {
+            loop {
+                if slots == 1 {
+                    eprintln!("> The index corresponding to the version to keep (default 0), or `i` to ignore :");
+                } else {
+                    eprintln!(
+                        "> The {} indices corresponding to the versions to keep (default `{}`), or `i` to ignore :",
+                        slots,
+                        (0..slots).map(|i| i.to_string()).collect::<Vec<_>>().join(" ")
+                    );
+                }
+                let input = self.read_line()?;
+
+                if input.is_empty() {
+                    break Ok(Some(amb.default_choice()));
+                } else if input == "i" {
+                    break Ok(None);
+                } else {
+                    match parse_indices(&input, slots, amb.versions.len()) {
+                        Ok(numbers) => break Ok(Some(numbers)),
+                        Err(err) => eprintln!("WWW {}", err),
+                    }
+                }
+            }
+        }
+    }
+
+    fn confirm_removal(&mut self, files: &[&Path]) -> Result<bool, NoAnswer> {
+        if !self.auto_confirm_level.is_at_least_removal() || files.is_empty() {
+            return Ok(true);
+        }
+
+        eprintln!("\n------------");
+        eprintln!("Are you agreeing to these removals ? Type `y` and press enter if you do.");
+        Ok(self.read_line()? == "y")
+    }
+}
+
+/// Answers from a script, mostly for tests.
+///
+/// Once the script is exhausted, there is no answer anymore.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedPrompter {
+    pub choices: VecDeque<Option<Vec<usize>>>,
+    pub confirmations: VecDeque<bool>,
+}
+
+impl ScriptedPrompter {
+    pub fn new<C, F>(choices: C, confirmations: F) -> Self
+    where
+        C: IntoIterator<Item = Option<Vec<usize>>>,
+        F: IntoIterator<Item = bool>,
+    {
+        ScriptedPrompter {
+            choices: choices.into_iter().collect(),
+            confirmations: confirmations.into_iter().collect(),
+        }
+    }
+}
+
+impl Prompter for ScriptedPrompter {
+    fn choose_versions(&mut self, _: &Ambiguity) -> Result<Option<Vec<usize>>, NoAnswer> {
+        self.choices
+            .pop_front()
+            .ok_or_else(|| NoAnswer("no more scripted choices".to_string()))
+    }
+
+    fn confirm_removal(&mut self, _: &[&Path]) -> Result<bool, NoAnswer> {
+        self.confirmations
+            .pop_front()
+            .ok_or_else(|| NoAnswer("no more scripted confirmations".to_string()))
+    }
+}
+
+/// Never asks anything and always takes the same decisions.
+#[derive(Debug, Copy, Clone)]
+pub struct PolicyPrompter {
+    /// Keep the default choice of ambiguities (the most recently built) instead of leaving them
+    pub keep_default: bool,
+    /// Agree to the removals
+    pub confirm: bool,
+}
+
+impl Prompter for PolicyPrompter {
+    fn choose_versions(&mut self, amb: &Ambiguity) -> Result<Option<Vec<usize>>, NoAnswer> {
+        Ok(self.keep_default.then(|| amb.default_choice()))
+    }
+
+    fn confirm_removal(&mut self, _: &[&Path]) -> Result<bool, NoAnswer> {
+        Ok(self.confirm)
+    }
+}
+
+/// Parses the user's answer to which versions to keep : `count` distinct indices lower than
+/// `len`, separated by spaces or commas.
+fn parse_indices(input: &str, count: usize, len: usize) -> Result<Vec<usize>, String> {
+    let mut numbers = Vec::with_capacity(count);
+    for n in input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+    {
+        let number = usize::from_str(n)
+            .map_err(|err| format!("Can't parse input `{}` into number : {}", n, err))?;
+        if number >= len {
+            return Err(format!(
+                "parsed number {} from `{}` is too high, please provide a number between 0 and {}.",
+                number, input, len
+            ));
+        } else if numbers.contains(&number) {
+            return Err(format!("index {} is given twice in `{}`.", number, input));
+        }
+        numbers.push(number);
+    }
+
+    if numbers.len() != count {
+        Err(format!(
+            "expected {} indices in `{}` but got {}.",
+            count,
+            input,
+            numbers.len()
+        ))
+    } else {
+        Ok(numbers)
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashSet;
+use std::fs::{copy, metadata, read_dir, remove_file, rename, File, FileTimes};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where `file` goes in the quarantine directory `dir`, `taken` being the paths given to the
+/// other files of the same run : files with the same name from different cache directories or
+/// from earlier runs are numbered the way the trash does, `name.2`, `name.3`, ...
+pub fn quarantine_path(file: &Path, dir: &Path, taken: &HashSet<PathBuf>) -> io::Result<PathBuf> {
+    let file_name = file
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path without a file name"))?;
+    let mut dest = dir.join(file_name);
+    let mut n = 1;
+    while taken.contains(&dest) || dest.symlink_metadata().is_ok() {
+        n += 1;
+        let mut name = file_name.to_os_string();
+        name.push(format!(".{}", n));
+        dest = dir.join(name);
+    }
+    Ok(dest)
+}
+
+/// Moves `from` to `to` : renamed when on the same filesystem, otherwise copied then removed,
+/// keeping its access and modification times.
+///
+/// Fails if `to` exists, instead of replacing it.
+pub fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("`{}` exists already", to.display()),
+        ));
+    }
+    match rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+            let metadata = metadata(from)?;
+            copy(from, to)?;
+            File::options().write(true).open(to)?.set_times(
+                FileTimes::new()
+                    .set_accessed(metadata.accessed()?)
+                    .set_modified(metadata.modified()?),
+            )?;
+            remove_file(from)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// When `file` has been put in quarantine.
+///
+/// The modification time is the one of the package, so this is the status change time, which
+/// moving or copying the file updates.
+pub fn quarantined_since(file: &Path) -> io::Result<SystemTime> {
+    let ctime = metadata(file)?.ctime();
+    Ok(UNIX_EPOCH + Duration::from_secs(u64::try_from(ctime).unwrap_or(0)))
+}
+
+/// Lists the files of the quarantine directory `dir` that have been there for more than
+/// `max_age`, sorted.
+pub fn expired_files(dir: &Path, max_age: Duration) -> io::Result<Vec<PathBuf>> {
+    let now = SystemTime::now();
+    let mut files = Vec::new();
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let since = quarantined_since(&path)?;
+        if now.duration_since(since).is_ok_and(|age| age > max_age) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
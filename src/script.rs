@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{absolute, Path};
+
+use crate::options::Options;
+use crate::plan::CleanupPlan;
+use crate::quarantine::quarantine_path;
+
+/// Writes a POSIX shell script removing the old packages of `plan` (or moving them to
+/// `opts.move_to`), each package preceded by a comment saying which versions are kept.
+///
+/// The paths are made absolute, so that the script can be run from any directory. The files
+/// are moved to the names [`quarantine_path`] gives them now, and are left alone if something
+/// took that name by the time the script runs.
+///
+/// The trash can't be handled by a script, the `.trashinfo` files would be missing.
+pub fn write_script<W: Write>(plan: &CleanupPlan, opts: &Options, mut out: W) -> io::Result<()> {
+    if opts.trash {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "a script can't move files to the trash",
+        ));
+    }
+
+    // Package name => (kept versions, removed packages)
+    let mut by_name: BTreeMap<&str, (Vec<&str>, Vec<_>)> = BTreeMap::new();
+    for p in plan.kept.iter() {
+        by_name.entry(&p.name).or_default().0.push(&p.pkgver);
+    }
+    for p in plan.old.iter() {
+        by_name.entry(&p.name).or_default().1.push(p);
+    }
+
+    writeln!(out, "#!/bin/sh")?;
+    write!(out, "# Removal of the old packages of ")?;
+    for (i, dir) in opts.dirs.iter().enumerate() {
+        if i > 0 {
+            write!(out, ", ")?;
+        }
+        write_escaped(&mut out, absolute(dir)?.as_os_str().as_bytes())?;
+    }
+    writeln!(out, ", generated by remove_old_arch_pkgs.")?;
+    writeln!(out, "# Review it before running it.")?;
+    writeln!(out)?;
+    let quarantine = opts.move_to.as_deref().map(absolute).transpose()?;
+    if let Some(quarantine) = &quarantine {
+        write!(out, "mkdir -p -- ")?;
+        write_quoted(&mut out, quarantine)?;
+        writeln!(out)?;
+        writeln!(out)?;
+    }
+
+    let mut taken = HashSet::new();
+    for (name, (kept, old)) in by_name.iter().filter(|(_, (_, old))| !old.is_empty()) {
+        let comment = format!(
+            "{} : keeping {}, removing {}",
+            name,
+            if kept.is_empty() {
+                "nothing".to_string()
+            } else {
+                kept.join(" ")
+            },
+            old.iter()
+                .map(|p| &p.pkgver[..])
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        write!(out, "# ")?;
+        write_escaped(&mut out, comment.as_bytes())?;
+        writeln!(out)?;
+        for file in old.iter().flat_map(|p| p.files()) {
+            let file = &absolute(file)?;
+            match &quarantine {
+                Some(quarantine) => {
+                    let dest = quarantine_path(file, quarantine, &taken)?;
+                    write!(out, "if [ -e ")?;
+                    write_quoted(&mut out, &dest)?;
+                    write!(out, " ] || [ -L ")?;
+                    write_quoted(&mut out, &dest)?;
+                    write!(out, " ]; then echo \"skipping \"")?;
+                    write_quoted(&mut out, file)?;
+                    write!(out, "\" : destination exists\" >&2; else mv -- ")?;
+                    write_quoted(&mut out, file)?;
+                    write!(out, " ")?;
+                    write_quoted(&mut out, &dest)?;
+                    writeln!(out, "; fi")?;
+                    taken.insert(dest);
+                }
+                None => {
+                    write!(out, "rm -- ")?;
+                    write_quoted(&mut out, file)?;
+                    writeln!(out)?;
+                }
+            }
+        }
+        writeln!(out)?;
+    }
+
+    out.flush()
+}
+
+/// Writes `text` in a comment, its control characters being written as `\xNN` : a newline in
+/// a file name would end the comment and run the rest of the name as a command.
+fn write_escaped<W: Write>(out: &mut W, text: &[u8]) -> io::Result<()> {
+    for &b in text {
+        if b.is_ascii_control() {
+            write!(out, "\\x{:02x}", b)?;
+        } else {
+            out.write_all(&[b])?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `path` between single quotes, the quotes it contains being written as `'\''`.
+fn write_quoted<W: Write>(out: &mut W, path: &Path) -> io::Result<()> {
+    out.write_all(b"'")?;
+    for (i, part) in path
+        .as_os_str()
+        .as_bytes()
+        .split(|&b| b == b'\'')
+        .enumerate()
+    {
+        // Every part but the first was preceded by a quote.
+        if i > 0 {
+            out.write_all(b"'\\''")?;
+        }
+        out.write_all(part)?;
+    }
+    out.write_all(b"'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    fn quoted(path: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_quoted(&mut out, Path::new(OsStr::from_bytes(path))).unwrap();
+        out
+    }
+
+    fn escaped(text: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_escaped(&mut out, text).unwrap();
+        out
+    }
+
+    #[test]
+    fn write_quoted_paths() {
+        assert_eq!(
+            quoted(b"/cache/foo-1-1.pkg.tar.zst"),
+            b"'/cache/foo-1-1.pkg.tar.zst'"
+        );
+        assert_eq!(quoted(b"/cache/it's"), br"'/cache/it'\''s'");
+        assert_eq!(quoted(b"'a'"), br"''\''a'\'''");
+        // Everything else is literal between single quotes.
+        assert_eq!(quoted(b"$(rm -rf /)\n`x`"), b"'$(rm -rf /)\n`x`'");
+        assert_eq!(quoted(b"a\xffb"), b"'a\xffb'");
+    }
+
+    #[test]
+    fn write_escaped_comments() {
+        assert_eq!(escaped(b"foo : keeping 1-1"), b"foo : keeping 1-1");
+        assert_eq!(
+            escaped(b"foo-1\ntouch PWNED\n-1"),
+            br"foo-1\x0atouch PWNED\x0a-1"
+        );
+        assert_eq!(escaped(b"a\rb\x7f\x1b"), br"a\x0db\x7f\x1b");
+        // Only control characters are escaped.
+        assert_eq!(escaped("caf\u{e9}".as_bytes()), "caf\u{e9}".as_bytes());
+    }
+}
@@ -0,0 +1,233 @@
+use std::env::var_os;
+use std::fs::{metadata, remove_file, symlink_metadata, DirBuilder, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// A trash directory of the freedesktop.org trash specification, with its `files` and `info`
+/// subdirectories.
+#[derive(Debug, Clone)]
+pub struct Trash {
+    pub dir: PathBuf,
+}
+
+impl Trash {
+    /// The home trash : `$XDG_DATA_HOME/Trash`, `~/.local/share/Trash` by default.
+    pub fn home() -> io::Result<Self> {
+        let data_home = match (var_os("XDG_DATA_HOME"), var_os("HOME")) {
+            (Some(data), _) if !data.is_empty() => PathBuf::from(data),
+            (_, Some(home)) if !home.is_empty() => Path::new(&home).join(".local/share"),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "neither `XDG_DATA_HOME` nor `HOME` is set",
+                ))
+            }
+        };
+        Ok(Trash {
+            dir: data_home.join("Trash"),
+        })
+    }
+
+    /// The trash where `file` should go : the home trash if it is on the same filesystem,
+    /// otherwise the one at the top of its filesystem, `$topdir/.Trash/$uid` if the
+    /// administrator set it up, `$topdir/.Trash-$uid` else.
+    ///
+    /// Falls back to the home trash if there can't be a trash on the filesystem of `file`.
+    pub fn for_file(file: &Path) -> io::Result<Self> {
+        let home = Trash::home()?;
+        let dev = metadata(file)?.dev();
+        if existing_ancestor_dev(&home.dir)? == dev {
+            return Ok(home);
+        }
+
+        let top = top_dir(file, dev)?;
+        let uid = unsafe { libc::getuid() };
+        let shared = top.join(".Trash");
+        // The shared trash must be a real directory with the sticky bit, not a symlink.
+        if let Ok(m) = symlink_metadata(&shared) {
+            if m.is_dir() && m.permissions().mode() & 0o1000 != 0 {
+                let trash = Trash {
+                    dir: shared.join(uid.to_string()),
+                };
+                if trash.create_dirs().is_ok() {
+                    return Ok(trash);
+                }
+            }
+        }
+        let trash = Trash {
+            dir: top.join(format!(".Trash-{}", uid)),
+        };
+        match trash.create_dirs() {
+            Ok(()) => Ok(trash),
+            Err(_) => Ok(home),
+        }
+    }
+
+    /// Reserves a name in the trash for `file` by writing its `.trashinfo`, and returns where
+    /// the file has to be moved.
+    pub fn reserve(&self, file: &Path) -> io::Result<PathBuf> {
+        self.create_dirs()?;
+        let file = std::path::absolute(file)?;
+        let file_name = file.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "path without a file name")
+        })?;
+
+        let mut n = 1;
+        loop {
+            let mut name = file_name.to_os_string();
+            if n > 1 {
+                name.push(format!(".{}", n));
+            }
+            let mut info_name = name.clone();
+            info_name.push(".trashinfo");
+
+            let dest = self.dir.join("files").join(&name);
+            let info_path = self.dir.join("info").join(info_name);
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&info_path)
+            {
+                // A file without its `.trashinfo`, the name is taken anyway.
+                Ok(_) if dest.exists() => remove_file(&info_path)?,
+                Ok(mut info) => {
+                    write!(
+                        info,
+                        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                        percent_encode(file.as_os_str().as_bytes()),
+                        deletion_date()
+                    )?;
+                    return Ok(dest);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(err) => return Err(err),
+            }
+            n += 1;
+        }
+    }
+
+    fn create_dirs(&self) -> io::Result<()> {
+        let mut builder = DirBuilder::new();
+        builder.recursive(true).mode(0o700);
+        builder.create(self.dir.join("files"))?;
+        builder.create(self.dir.join("info"))
+    }
+}
+
+/// Removes the `.trashinfo` of `trashed`, a file in the `files` directory of a trash, once it
+/// has been restored or couldn't be moved there.
+///
+/// Does nothing if `trashed` isn't in a trash.
+pub fn forget(trashed: &Path) -> io::Result<()> {
+    let (files_dir, file_name) = match (trashed.parent(), trashed.file_name()) {
+        (Some(files_dir), Some(file_name)) if files_dir.ends_with("files") => {
+            (files_dir, file_name)
+        }
+        _ => return Ok(()),
+    };
+    let mut info_name = file_name.to_os_string();
+    info_name.push(".trashinfo");
+    let info = files_dir.with_file_name("info").join(info_name);
+
+    match remove_file(info) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Device of `path`, or of its nearest existing ancestor.
+fn existing_ancestor_dev(path: &Path) -> io::Result<u64> {
+    let mut path = path;
+    loop {
+        match metadata(path) {
+            Ok(m) => return Ok(m.dev()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => match path.parent() {
+                Some(parent) => path = parent,
+                None => return Err(err),
+            },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// The mount point of the filesystem `dev` which `file` is on.
+fn top_dir(file: &Path, dev: u64) -> io::Result<PathBuf> {
+    let mut top = std::path::absolute(file)?;
+    while let Some(parent) = top.parent() {
+        if metadata(parent)?.dev() != dev {
+            break;
+        }
+        top = parent.to_path_buf();
+    }
+    Ok(top)
+}
+
+/// Encodes `path` as in an URL, `/` apart.
+pub(crate) fn percent_encode(path: &[u8]) -> String {
+    path.iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Decodes what [`percent_encode`] encoded, `None` if a `%` isn't followed by two hex digits.
+pub(crate) fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = text.bytes();
+    let mut decoded = Vec::with_capacity(text.len());
+    while let Some(b) = bytes.next() {
+        if b != b'%' {
+            decoded.push(b);
+            continue;
+        }
+        let hex = [bytes.next()?, bytes.next()?];
+        let hex = std::str::from_utf8(&hex).ok()?;
+        decoded.push(u8::from_str_radix(hex, 16).ok()?);
+    }
+    Some(decoded)
+}
+
+/// Now, as `YYYY-MM-DDThh:mm:ss` in local time.
+#[cfg(feature = "chrono")]
+fn deletion_date() -> String {
+    chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Now, as `YYYY-MM-DDThh:mm:ss` in UTC : the local time zone isn't known without `chrono`.
+#[cfg(not(feature = "chrono"))]
+fn deletion_date() -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, time) = (secs / 86400, secs % 86400);
+
+    // Civil date from the number of days since the epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
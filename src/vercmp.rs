@@ -0,0 +1,239 @@
+//! Pure Rust port of libalpm's `alpm_pkg_vercmp`, so we consider newer exactly what pacman
+//! considers newer.
+//!
+//! Versions are `[epoch:]pkgver[-pkgrel]` : the epoch is compared first, then `pkgver` with
+//! the rpmvercmp segment rules, then `pkgrel` (sub-releases like `1.1` included) if both have
+//! one.
+
+use std::cmp::Ordering;
+
+/// Compares two full package versions like pacman's `vercmp` does.
+///
+/// Example :
+/// ```
+/// use std::cmp::Ordering;
+/// use remove_old_arch_pkgs::vercmp;
+///
+/// assert_eq!(vercmp("5.3.1.arch1-1", "5.3.arch1-1"), Ordering::Greater);
+/// assert_eq!(vercmp("2:1.0-1", "1.5-3"), Ordering::Greater);
+/// assert_eq!(vercmp("1.0+1+g1bcc8585-1", "1.0-1"), Ordering::Greater);
+/// assert_eq!(vercmp("1.0rc1-1", "1.0-1"), Ordering::Less);
+/// assert_eq!(vercmp("1.0-1.1", "1.0-1"), Ordering::Greater);
+/// ```
+pub fn vercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (epoch_a, ver_a, rel_a) = parse_evr(a);
+    let (epoch_b, ver_b, rel_b) = parse_evr(b);
+
+    rpmvercmp(epoch_a, epoch_b)
+        .then_with(|| rpmvercmp(ver_a, ver_b))
+        .then_with(|| match (rel_a, rel_b) {
+            (Some(rel_a), Some(rel_b)) => rpmvercmp(rel_a, rel_b),
+            _ => Ordering::Equal,
+        })
+}
+
+/// Splits `[epoch:]pkgver[-pkgrel]` into its three parts, the epoch defaulting to `0`.
+fn parse_evr(evr: &str) -> (&str, &str, Option<&str>) {
+    let digits_len = evr.bytes().take_while(u8::is_ascii_digit).count();
+
+    let (epoch, rest) = if evr[digits_len..].starts_with(':') {
+        let epoch = &evr[..digits_len];
+        (
+            if epoch.is_empty() { "0" } else { epoch },
+            &evr[(digits_len + 1)..],
+        )
+    } else {
+        ("0", evr)
+    };
+
+    match rest.rfind('-') {
+        Some(i) => (epoch, &rest[..i], Some(&rest[(i + 1)..])),
+        None => (epoch, rest, None),
+    }
+}
+
+/// Compares two version segments (no epoch nor release) the way rpm (and thus pacman) does.
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    // `one` and `two` are the starts of the current segments, `ptr1` and `ptr2` their ends.
+    let (mut one, mut two) = (0, 0);
+    let (mut ptr1, mut ptr2) = (0, 0);
+
+    while one < a.len() && two < b.len() {
+        while one < a.len() && !a[one].is_ascii_alphanumeric() {
+            one += 1;
+        }
+        while two < b.len() && !b[two].is_ascii_alphanumeric() {
+            two += 1;
+        }
+
+        // If we ran to the end of either, we are finished with the loop.
+        if one >= a.len() || two >= b.len() {
+            break;
+        }
+
+        // If the separator lengths were different, we are also finished.
+        if one - ptr1 != two - ptr2 {
+            return (one - ptr1).cmp(&(two - ptr2));
+        }
+
+        ptr1 = one;
+        ptr2 = two;
+
+        // Grab first completely alpha or completely numeric segment.
+        let is_num = a[ptr1].is_ascii_digit();
+        let in_segment: fn(&u8) -> bool = if is_num {
+            u8::is_ascii_digit
+        } else {
+            u8::is_ascii_alphabetic
+        };
+        ptr1 += a[ptr1..].iter().take_while(|c| in_segment(c)).count();
+        ptr2 += b[ptr2..].iter().take_while(|c| in_segment(c)).count();
+
+        // Segments of different types : numeric ones are always newer than alpha ones.
+        if two == ptr2 {
+            return if is_num {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+
+        let (mut seg1, mut seg2) = (&a[one..ptr1], &b[two..ptr2]);
+        if is_num {
+            // Throw away any leading zeros, then whichever number has more digits wins.
+            while seg1.first() == Some(&b'0') {
+                seg1 = &seg1[1..];
+            }
+            while seg2.first() == Some(&b'0') {
+                seg2 = &seg2[1..];
+            }
+            match seg1.len().cmp(&seg2.len()) {
+                Ordering::Equal => (),
+                ord => return ord,
+            }
+        }
+
+        match seg1.cmp(seg2) {
+            Ordering::Equal => (),
+            ord => return ord,
+        }
+
+        one = ptr1;
+        two = ptr2;
+    }
+
+    // All segments compared identically but the separating characters were different.
+    if one >= a.len() && two >= b.len() {
+        return Ordering::Equal;
+    }
+
+    // The final showdown : we never want a remaining alpha string to beat an empty string.
+    // - if `a` is empty and `b` is not an alpha, `b` is newer.
+    // - if `a` is an alpha, `b` is newer.
+    // - otherwise `a` is newer.
+    let a_is_alpha = a.get(one).is_some_and(u8::is_ascii_alphabetic);
+    let b_is_alpha = b.get(two).is_some_and(u8::is_ascii_alphabetic);
+    if (one >= a.len() && !b_is_alpha) || a_is_alpha {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cases of pacman's `test/util/vercmptest.sh`, plus sub-releases : `(a, b, a vs b)`.
+    const CASES: &[(&str, &str, Ordering)] = {
+        use Ordering::*;
+        &[
+            // all similar length, no pkgrel
+            ("1.5.0", "1.5.0", Equal),
+            ("1.5.1", "1.5.0", Greater),
+            // mixed length
+            ("1.5.1", "1.5", Greater),
+            // with pkgrel, simple
+            ("1.5.0-1", "1.5.0-1", Equal),
+            ("1.5.0-1", "1.5.0-2", Less),
+            ("1.5.0-1", "1.5.1-1", Less),
+            ("1.5.0-2", "1.5.1-1", Less),
+            // with pkgrel, mixed lengths
+            ("1.5-1", "1.5.1-1", Less),
+            ("1.5-2", "1.5.1-1", Less),
+            ("1.5-2", "1.5.1-2", Less),
+            // mixed pkgrel inclusion
+            ("1.5", "1.5-1", Equal),
+            ("1.5-1", "1.5", Equal),
+            ("1.1-1", "1.1", Equal),
+            ("1.0-1", "1.1", Less),
+            ("1.1-1", "1.0", Greater),
+            // alphanumeric versions
+            ("1.5b-1", "1.5-1", Less),
+            ("1.5b", "1.5", Less),
+            ("1.5b-1", "1.5", Less),
+            ("1.5b", "1.5.1", Less),
+            // from the manpage
+            ("1.0a", "1.0alpha", Less),
+            ("1.0alpha", "1.0b", Less),
+            ("1.0b", "1.0beta", Less),
+            ("1.0beta", "1.0rc", Less),
+            ("1.0rc", "1.0", Less),
+            ("1.0a", "1.0", Less),
+            // alpha-dotted versions
+            ("1.5.a", "1.5", Greater),
+            ("1.5.b", "1.5.a", Greater),
+            ("1.5.1", "1.5.b", Greater),
+            // alpha dots and dashes
+            ("1.5.b-1", "1.5.b", Equal),
+            ("1.5-1", "1.5.b", Less),
+            // same/similar content, differing separators
+            ("2.0", "2_0", Equal),
+            ("2.0_a", "2_0.a", Equal),
+            ("2.0a", "2.0.a", Less),
+            ("2___a", "2_a", Greater),
+            // epoch included version comparisons
+            ("0:1.0", "0:1.0", Equal),
+            ("0:1.0", "0:1.1", Less),
+            ("1:1.0", "0:1.0", Greater),
+            ("1:1.0", "0:1.1", Greater),
+            ("1:1.0", "2:1.1", Less),
+            // epoch + sometimes present pkgrel
+            ("1:1.0", "0:1.0-1", Greater),
+            ("1:1.0-1", "0:1.1-1", Greater),
+            // epoch included on one version
+            ("0:1.0", "1.0", Equal),
+            ("0:1.0", "1.1", Less),
+            ("0:1.1", "1.0", Greater),
+            ("1:1.0", "1.0", Greater),
+            ("1:1.0", "1.1", Greater),
+            ("1:1.1", "1.1", Greater),
+            // an empty epoch is `0`
+            (":1.0", "1.0", Equal),
+            (":1.0", "1:1.0", Less),
+            // sub-releases
+            ("1.0-1.1", "1.0-1", Greater),
+            ("1.0-1.1", "1.0-2", Less),
+            ("1.0-1.2", "1.0-1.1", Greater),
+            ("1.0-1.10", "1.0-1.9", Greater),
+            ("1.0-1.1", "1.0", Equal),
+        ]
+    };
+
+    #[test]
+    fn vercmptest() {
+        for &(a, b, expected) in CASES {
+            assert_eq!(vercmp(a, b), expected, "vercmp({:?}, {:?})", a, b);
+            assert_eq!(vercmp(b, a), expected.reverse(), "vercmp({:?}, {:?})", b, a);
+        }
+    }
+}